@@ -0,0 +1,82 @@
+// Nearest hits among thousands of spheres, through the scene's BVH and with
+// a plain loop over the same spheres, then a small render of the scene.
+//
+//     cargo bench --bench bvh
+
+use std::time::{Duration, Instant};
+
+use raytracer::III_vector::Vec3;
+use raytracer::camera::{Camera, Projection};
+use raytracer::objects::{Light, LightOptions, Scene, Shape, Sphere, SurfaceOptions, SurfaceType};
+use raytracer::render::{render, Settings};
+use raytracer::sampling::Rng;
+use raytracer::PPM::RGB;
+
+const RAYS: usize = 20_000;
+
+fn spheres(n: usize, rng: &mut Rng) -> Vec<Sphere> {
+    // spread over a cube holding about one per unit of volume
+    let side = (n as f32).cbrt().ceil();
+    let opt = SurfaceOptions::new(0.5, 1.0, 1.0, 10.0, SurfaceType::Solid(RGB::new(200, 120, 60)));
+    (0..n)
+        .map(|_| {
+            let p = Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()).scale(side);
+            Sphere::new(p + Vec3::new(2.0, -side / 2.0, -side / 2.0), 0.3, opt).unwrap()
+        })
+        .collect()
+}
+
+fn rays(n: usize, side: f32, rng: &mut Rng) -> Vec<(Vec3<f32>, Vec3<f32>)> {
+    (0..n)
+        .map(|_| {
+            let target = Vec3::new(2.0 + side * rng.next_f32(), side * (rng.next_f32() - 0.5), side * (rng.next_f32() - 0.5));
+            let root = Vec3::new(0.0, 0.0, 0.0);
+            (root, (target - root).norm())
+        })
+        .collect()
+}
+
+fn time<F: FnMut() -> usize>(mut f: F) -> (Duration, usize) {
+    let start = Instant::now();
+    let found = f();
+    (start.elapsed(), found)
+}
+
+fn main() {
+    println!("{:>8} {:>14} {:>14} {:>8} {:>12}", "spheres", "linear", "bvh", "speedup", "render");
+    for &n in &[100, 1_000, 10_000] {
+        let mut rng = Rng::new(n as u64);
+        let objects = spheres(n, &mut rng);
+        let side = (n as f32).cbrt().ceil();
+        let rays = rays(RAYS, side, &mut rng);
+
+        let mut scene = Scene::new();
+        for s in &objects {
+            scene.add_obj(*s);
+        }
+        scene.add_light(Light::new(Vec3::new(-1.0, 2.0, side), LightOptions::new(10.0, 100.0, 5.0)));
+        // the tree is built here, not in the timed loop
+        scene.ind_viewing(rays[0].0, rays[0].1);
+
+        let (linear, a) = time(|| {
+            rays.iter()
+                .filter(|(root, dir)| {
+                    objects.iter()
+                        .filter_map(|s| Shape::ray_intersect(s, *root, *dir))
+                        .fold(None, |best: Option<f32>, t| Some(best.map_or(t, |b| b.min(t))))
+                        .is_some()
+                })
+                .count()
+        });
+        let (bvh, b) = time(|| rays.iter().filter(|(root, dir)| scene.ind_viewing(*root, *dir).is_some()).count());
+        assert_eq!(a, b, "both searches must see the same rays hit");
+
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0),
+            60.0, 1.0, Projection::Perspective).unwrap();
+        let settings = Settings { width: 160, height: 160, threads: 1, ..Settings::default() };
+        let (frame, _) = time(|| render(&scene, &camera, &settings).width() as usize);
+
+        println!("{:>8} {:>12.1?} {:>12.1?} {:>7.1}x {:>12.1?}", n, linear, bvh,
+            linear.as_secs_f64() / bvh.as_secs_f64(), frame);
+    }
+}
@@ -0,0 +1,300 @@
+// Axis aligned bounding boxes and a bounding volume hierarchy over them.
+//
+// The tree is built top-down with the surface area heuristic, the split
+// being picked among a few bins of the item centers on each axis. Nodes are
+// stored flat, depth first : the left child of a node is the next one.
+
+use crate::III_vector::Vec3;
+
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3<f32>,
+    pub max: Vec3<f32>,
+}
+
+fn axis(v: Vec3<f32>, i: usize) -> f32 {
+    match i {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+impl Aabb {
+    // the box holding both corners, in any order
+    pub fn new(a: Vec3<f32>, b: Vec3<f32>) -> Aabb {
+        Aabb::empty().grow(a).grow(b)
+    }
+
+    // holds nothing, the neutral element of union()
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points(points: &[Vec3<f32>]) -> Aabb {
+        points.iter().fold(Aabb::empty(), |b, p| b.grow(*p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.min.x <= self.max.x && self.min.y <= self.max.y && self.min.z <= self.max.z)
+    }
+
+    pub fn grow(self, p: Vec3<f32>) -> Aabb {
+        Aabb {
+            min: Vec3::new(self.min.x.min(p.x), self.min.y.min(p.y), self.min.z.min(p.z)),
+            max: Vec3::new(self.max.x.max(p.x), self.max.y.max(p.y), self.max.z.max(p.z)),
+        }
+    }
+
+    pub fn union(self, other: Aabb) -> Aabb {
+        self.grow(other.min).grow(other.max)
+    }
+
+    pub fn center(&self) -> Vec3<f32> {
+        (self.min + self.max).scale(0.5)
+    }
+
+    // surface area, 0 for an empty box
+    pub fn area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    pub fn corners(&self) -> [Vec3<f32>; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z), Vec3::new(b.x, a.y, a.z),
+            Vec3::new(a.x, b.y, a.z), Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z), Vec3::new(b.x, a.y, b.z),
+            Vec3::new(a.x, b.y, b.z), Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    // slab test : where the ray enters the box, 0 if it starts inside ;
+    // `inv` holds 1 / dir on every axis. None if the box is missed or only
+    // met beyond `t_max`
+    pub fn hit(&self, root: Vec3<f32>, inv: Vec3<f32>, t_max: f32) -> Option<f32> {
+        let (mut t0, mut t1) = (0.0f32, t_max);
+        for i in 0..3 {
+            let (o, k) = (axis(root, i), axis(inv, i));
+            let a = (axis(self.min, i) - o) * k;
+            let b = (axis(self.max, i) - o) * k;
+            // min() and max() skip the NaN of a ray lying on a flat box
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+        // a little slack, the shape and its box round differently
+        if t0 <= t1 * (1.0 + 1e-6) { Some(t0) } else { None }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    bounds: Aabb,
+    // a leaf holds items[first..first + count] ; an inner node has
+    // count = 0 and its right child at `first`
+    first: usize,
+    count: usize,
+}
+
+// the items of a BVH are indices into the list of boxes it was built from
+#[derive(Clone)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    items: Vec<usize>,
+}
+
+const BINS: usize = 12;
+// leaves above this size are split even when the heuristic would keep them
+const MAX_LEAF: usize = 4;
+
+impl Bvh {
+    pub fn new(bounds: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            items: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            let centers: Vec<Vec3<f32>> = bounds.iter().map(|b| b.center()).collect();
+            bvh.build(bounds, &centers, 0, bounds.len());
+        }
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // the box around every item
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |n| n.bounds)
+    }
+
+    pub fn depth(&self) -> usize {
+        fn below(nodes: &[Node], n: usize) -> usize {
+            if nodes[n].count > 0 {
+                1
+            } else {
+                1 + below(nodes, n + 1).max(below(nodes, nodes[n].first))
+            }
+        }
+        if self.nodes.is_empty() { 0 } else { below(&self.nodes, 0) }
+    }
+
+    // returns the index of the new node
+    fn build(&mut self, bounds: &[Aabb], centers: &[Vec3<f32>], first: usize, count: usize) -> usize {
+        let node = self.nodes.len();
+        let items = &mut self.items[first..first + count];
+        let all = items.iter().fold(Aabb::empty(), |b, &i| b.union(bounds[i]));
+        self.nodes.push(Node { bounds: all, first, count });
+        if count == 1 {
+            return node;
+        }
+
+        let spread = Aabb::from_points(&items.iter().map(|&i| centers[i]).collect::<Vec<_>>());
+        // cost of a split relative to testing every item of a leaf, the
+        // chance to enter a child being the ratio of the areas
+        let mut best: Option<(f32, usize, usize)> = None;
+        for ax in 0..3 {
+            let (lo, hi) = (axis(spread.min, ax), axis(spread.max, ax));
+            if hi - lo <= 0.0 {
+                continue;
+            }
+            let k = BINS as f32 / (hi - lo);
+            let mut bins = [(Aabb::empty(), 0usize); BINS];
+            for &i in items.iter() {
+                let b = (((axis(centers[i], ax) - lo) * k) as usize).min(BINS - 1);
+                bins[b].0 = bins[b].0.union(bounds[i]);
+                bins[b].1 += 1;
+            }
+            // right side of every split, from the last bin back
+            let mut right = [(0.0f32, 0usize); BINS];
+            let mut acc = (Aabb::empty(), 0);
+            for b in (1..BINS).rev() {
+                acc = (acc.0.union(bins[b].0), acc.1 + bins[b].1);
+                right[b] = (acc.0.area(), acc.1);
+            }
+            let mut left = (Aabb::empty(), 0);
+            for b in 0..BINS - 1 {
+                left = (left.0.union(bins[b].0), left.1 + bins[b].1);
+                let (r_area, r_count) = right[b + 1];
+                if left.1 == 0 || r_count == 0 {
+                    continue;
+                }
+                let cost = 1.0 + (left.0.area() * left.1 as f32 + r_area * r_count as f32) / all.area().max(1e-20);
+                if best.is_none_or(|(c, _, _)| cost < c) {
+                    best = Some((cost, ax, b));
+                }
+            }
+        }
+
+        // all centers at the same place : nothing to split
+        let (cost, ax, split) = match best {
+            Some(b) => b,
+            None => return node,
+        };
+        if cost >= count as f32 && count <= MAX_LEAF {
+            return node;
+        }
+
+        let (lo, hi) = (axis(spread.min, ax), axis(spread.max, ax));
+        let k = BINS as f32 / (hi - lo);
+        let mut mid = 0;
+        for j in 0..count {
+            let i = items[j];
+            if (((axis(centers[i], ax) - lo) * k) as usize).min(BINS - 1) <= split {
+                items.swap(j, mid);
+                mid += 1;
+            }
+        }
+
+        self.nodes[node].count = 0;
+        self.build(bounds, centers, first, mid);
+        let right = self.build(bounds, centers, first + mid, count - mid);
+        self.nodes[node].first = right;
+        node
+    }
+
+    // the nearest item along a ray and its distance ; `hit(i)` gives the
+    // distance to item `i` along the unit `dir`, if it is hit at all. Ties
+    // go to the smaller index, as with a plain loop over the items
+    pub fn nearest<F>(&self, root: Vec3<f32>, dir: Vec3<f32>, mut hit: F) -> Option<(usize, f32)>
+        where F: FnMut(usize) -> Option<f32> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv = Vec3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z);
+        let mut best: Option<(usize, f32)> = None;
+        let mut stack = Vec::with_capacity(32);
+        stack.push(0);
+
+        while let Some(n) = stack.pop() {
+            let node = self.nodes[n];
+            let t_max = best.map_or(f32::INFINITY, |(_, t)| t);
+            if node.bounds.hit(root, inv, t_max).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                for &i in &self.items[node.first..node.first + node.count] {
+                    if let Some(t) = hit(i) {
+                        if best.is_none_or(|(j, b)| t < b || (t == b && i < j)) {
+                            best = Some((i, t));
+                        }
+                    }
+                }
+                continue;
+            }
+            // the nearer child is looked at first, so that it can cut the
+            // other one off
+            let (l, r) = (n + 1, node.first);
+            let tl = self.nodes[l].bounds.hit(root, inv, t_max);
+            let tr = self.nodes[r].bounds.hit(root, inv, t_max);
+            match (tl, tr) {
+                (Some(a), Some(b)) if a <= b => stack.extend_from_slice(&[r, l]),
+                (Some(_), Some(_)) => stack.extend_from_slice(&[l, r]),
+                (Some(_), None) => stack.push(l),
+                (None, Some(_)) => stack.push(r),
+                (None, None) => {}
+            }
+        }
+        best
+    }
+
+    // whether some item is hit closer than `t_max`, for shadow rays : the
+    // search stops at the first one found, whatever its distance
+    pub fn any_hit<F>(&self, root: Vec3<f32>, dir: Vec3<f32>, t_max: f32, mut hit: F) -> bool
+        where F: FnMut(usize) -> Option<f32> {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let inv = Vec3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z);
+        let mut stack = Vec::with_capacity(32);
+        stack.push(0);
+
+        while let Some(n) = stack.pop() {
+            let node = self.nodes[n];
+            if node.bounds.hit(root, inv, t_max).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                let items = &self.items[node.first..node.first + node.count];
+                if items.iter().any(|&i| hit(i).is_some_and(|t| t < t_max)) {
+                    return true;
+                }
+            } else {
+                stack.extend_from_slice(&[node.first, n + 1]);
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,196 @@
+// Turns pixel positions into primary rays.
+
+use std::f32::consts::PI;
+
+use crate::III_vector::{Vec3, Ray};
+use crate::sampling::concentric_disk;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Projection {
+    // pinhole, the field of view spans the picture height
+    Perspective,
+    // parallel rays, `height` is the height of the view in scene units
+    Orthographic { height: f32 },
+    // the whole sphere of directions : longitude along the width, latitude
+    // along the height, the view direction in the middle
+    Equirectangular,
+    // equidistant fisheye, the field of view (up to 360) spans the picture
+    // height ; nothing is seen past the circle where it turns all the way
+    // back
+    Fisheye,
+}
+
+#[derive(Clone, Copy)]
+pub struct Camera {
+    eye: Vec3<f32>,
+    forward: Vec3<f32>,
+    right: Vec3<f32>,
+    up: Vec3<f32>,
+    fov: f32,
+    aspect: f32,
+    half_w: f32,
+    half_h: f32,
+    projection: Projection,
+    // thin lens, everything is sharp while the aperture is 0
+    aperture: f32,
+    focus_distance: f32,
+}
+
+impl Camera {
+    // perspective camera : `fov` is the vertical field of view in degrees
+    // and `aspect` the width of the picture over its height
+    pub fn look_at(eye: Vec3<f32>, target: Vec3<f32>, up: Vec3<f32>, fov: f32, aspect: f32) -> Option<Camera> {
+        Camera::new(eye, target, up, fov, aspect, Projection::Perspective)
+    }
+
+    // None if the eye is on the target, `up` is along the line of sight or
+    // `fov` does not suit the projection (below 180 degrees for perspective,
+    // up to 360 for fisheye, ignored by the others)
+    pub fn new(eye: Vec3<f32>, target: Vec3<f32>, up: Vec3<f32>, fov: f32, aspect: f32,
+               projection: Projection) -> Option<Camera> {
+        let view = target - eye;
+        if view * view <= 0.0 || !(aspect.is_finite() && aspect > 0.0) {
+            return None;
+        }
+        let params_ok = match projection {
+            Projection::Perspective => fov > 0.0 && fov < 180.0,
+            Projection::Fisheye => fov > 0.0 && fov <= 360.0,
+            Projection::Orthographic { height } => height.is_finite() && height > 0.0,
+            Projection::Equirectangular => true,
+        };
+        if !params_ok {
+            return None;
+        }
+        let forward = view.norm();
+        let right = Vec3::prod(forward, up);
+        // sin of the angle between `up` and the line of sight
+        if right.len() <= 1e-6 * up.len() {
+            return None;
+        }
+        let right = right.norm();
+        let half_h = match projection {
+            Projection::Perspective => (fov.to_radians() / 2.0).tan(),
+            _ => 1.0,
+        };
+
+        Some(Camera {
+            eye,
+            forward,
+            right,
+            up: Vec3::prod(right, forward),
+            fov: fov.to_radians(),
+            aspect,
+            half_w: half_h * aspect,
+            half_h,
+            projection,
+            aperture: 0.0,
+            focus_distance: view.len(),
+        })
+    }
+
+    // depth of field : primary rays start anywhere on a lens of radius
+    // `aperture` and meet again `focus_distance` away from the eye ; None for
+    // a negative aperture or a non-positive distance
+    pub fn with_lens(self, aperture: f32, focus_distance: f32) -> Option<Camera> {
+        if !(aperture.is_finite() && aperture >= 0.0 && focus_distance.is_finite() && focus_distance > 0.0) {
+            return None;
+        }
+        Some(Camera {
+            aperture,
+            focus_distance,
+            ..self
+        })
+    }
+
+    pub fn aperture(&self) -> f32 {
+        self.aperture
+    }
+
+    pub fn focus_distance(&self) -> f32 {
+        self.focus_distance
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn eye(&self) -> Vec3<f32> {
+        self.eye
+    }
+
+    // the ray through the point (lu, lv) of the lens, both in [0, 1) ; with a
+    // zero aperture it is just ray(u, v)
+    pub fn ray_through_lens(&self, u: f32, v: f32, lu: f32, lv: f32) -> Option<Ray<f32>> {
+        let pinhole = self.ray(u, v)?;
+        if self.aperture <= 0.0 {
+            return Some(pinhole);
+        }
+
+        // the point every ray of this pixel goes through : on the focus plane
+        // for the perspective, at focus distance along the ray otherwise
+        let dir = pinhole.dir.norm();
+        let t = match self.projection {
+            Projection::Perspective => self.focus_distance / (dir * self.forward),
+            _ => self.focus_distance,
+        };
+        let focus = pinhole.root + dir.scale(t);
+
+        let (dx, dy) = concentric_disk(lu, lv);
+        let root = pinhole.root + self.right.scale(dx * self.aperture) + self.up.scale(dy * self.aperture);
+        Some(Ray {
+            root,
+            dir: focus - root,
+        })
+    }
+
+    // (u, v) are in [0, 1] from the top left corner of the picture ; None
+    // where the projection sees nothing
+    pub fn ray(&self, u: f32, v: f32) -> Option<Ray<f32>> {
+        // position on the picture, in [-aspect, aspect] x [-1, 1] with +y up
+        let x = 2.0 * u - 1.0;
+        let y = 1.0 - 2.0 * v;
+
+        let ray = match self.projection {
+            Projection::Perspective => Ray {
+                root: self.eye,
+                dir: self.forward
+                    + self.right.scale(x * self.half_w)
+                    + self.up.scale(y * self.half_h),
+            },
+            Projection::Orthographic { height } => Ray {
+                root: self.eye
+                    + self.right.scale(x * self.aspect * height / 2.0)
+                    + self.up.scale(y * height / 2.0),
+                dir: self.forward,
+            },
+            Projection::Equirectangular => {
+                let lon = x * PI;
+                let lat = y * PI / 2.0;
+                Ray {
+                    root: self.eye,
+                    dir: self.forward.scale(lat.cos() * lon.cos())
+                        + self.right.scale(lat.cos() * lon.sin())
+                        + self.up.scale(lat.sin()),
+                }
+            }
+            Projection::Fisheye => {
+                let (px, py) = (x * self.aspect, y);
+                let r = (px * px + py * py).sqrt();
+                // angle from the view direction grows linearly with the
+                // distance from the center
+                let theta = r * self.fov / 2.0;
+                if theta > PI {
+                    return None;
+                }
+                let (sx, sy) = if r > 0.0 { (px / r, py / r) } else { (0.0, 0.0) };
+                Ray {
+                    root: self.eye,
+                    dir: self.forward.scale(theta.cos())
+                        + self.right.scale(theta.sin() * sx)
+                        + self.up.scale(theta.sin() * sy),
+                }
+            }
+        };
+        Some(ray)
+    }
+}
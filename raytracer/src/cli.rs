@@ -0,0 +1,213 @@
+use std::path::Path;
+use std::thread;
+
+use raytracer::PPM::ImageFormat;
+use raytracer::render::Adaptive;
+use raytracer::sampling::{Filter, Pattern};
+use raytracer::tonemap::{Operator, ToneMap, Transfer};
+
+pub const USAGE: &str = "\
+Usage: raytracer [OPTIONS] [SCENE]
+
+Renders SCENE (scenes/default.toml by default) into an image.
+
+Options:
+  -o, --output <PATH>   where to write the image, `-` for stdout
+                        (default: first free PictureN.ppm)
+      --no-clobber      fail instead of overwriting an existing output file
+      --width <N>       image width in pixels (default: 800)
+      --height <N>      image height in pixels (default: 800)
+      --fov <DEG>       vertical field of view in degrees (default: from the scene)
+      --depth <N>       max mirror bounces and glass passes of a ray (default: 4)
+      --samples <N>     rays per pixel, a square number for grid and jittered
+                        patterns (default: 1)
+      --pattern <P>     where samples go : grid, jittered, halton or sobol
+                        (default: grid)
+      --filter <F>      how samples are weighted : box, tent, gaussian or
+                        mitchell (default: box)
+      --adaptive <T>    shoot 4 rays per pixel first and the full --samples
+                        (above 4) only where they, or the neighbouring pixels,
+                        differ by more than T (0 to 1, e.g. 0.05)
+      --threads <N>     worker threads (default: number of cores)
+      --exposure <EV>   brightness change in stops before tone mapping (default: 0)
+      --tonemap <OP>    clamp, reinhard, aces or filmic (default: clamp)
+      --white <L>       luminance mapped to white by reinhard (default: none)
+      --transfer <TF>   linear, srgb or a gamma value like 2.2 (default: linear)
+      --format <FMT>    output format: ppm, png or hdr (default: guessed from the output path)
+  -h, --help            print this help
+";
+
+const MAX_SIDE: u32 = 16384;
+
+pub struct Options {
+    pub scene: String,
+    pub output: Option<String>,
+    pub no_clobber: bool,
+    pub width: u32,
+    pub height: u32,
+    pub fov: Option<f32>,
+    pub depth: Option<u32>,
+    pub samples: u32,
+    pub pattern: Pattern,
+    pub filter: Filter,
+    pub adaptive: Option<Adaptive>,
+    pub threads: usize,
+    pub format: ImageFormat,
+    pub tone_map: ToneMap,
+}
+
+pub enum Command {
+    Help,
+    Render(Options),
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut scene = None;
+    let mut output = None;
+    let mut no_clobber = false;
+    let mut width = 800;
+    let mut height = 800;
+    let mut fov = None;
+    let mut depth = None;
+    let mut samples = 1;
+    let mut pattern = Pattern::Grid;
+    let mut filter = Filter::Box;
+    let mut adaptive = None;
+    let mut threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut format = None;
+    let mut tone_map = ToneMap::default();
+    let mut white = None;
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            if scene.replace(arg).is_some() {
+                return Err("more than one scene file given".to_string());
+            }
+            continue;
+        }
+
+        // both `--opt value` and `--opt=value` are accepted
+        let (name, inline) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (arg[..i].to_string(), Some(arg[i + 1..].to_string())),
+            _ => (arg, None),
+        };
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--no-clobber" if inline.is_none() => {
+                no_clobber = true;
+                continue;
+            }
+            "-o" | "--output" | "--width" | "--height" | "--fov" | "--depth" |
+            "--samples" | "--pattern" | "--filter" | "--adaptive" | "--threads" | "--format" | "--exposure" | "--tonemap" |
+            "--white" | "--transfer" => {}
+            _ => return Err(format!("unknown option `{}`", name)),
+        }
+        let value = match inline {
+            Some(v) => v,
+            None => args.next().ok_or_else(|| format!("{} needs a value", name))?,
+        };
+
+        match name.as_str() {
+            "-o" | "--output" => output = Some(value),
+            "--width" => width = number(&name, &value, 1, MAX_SIDE)?,
+            "--height" => height = number(&name, &value, 1, MAX_SIDE)?,
+            "--fov" => {
+                let f: f32 = value.parse().map_err(|_| format!("{}: `{}` is not a number", name, value))?;
+                if !(f > 0.0 && f <= 360.0) {
+                    return Err(format!("{}: must be between 0 and 360 degrees, got {}", name, value));
+                }
+                fov = Some(f);
+            }
+            "--depth" => depth = Some(number(&name, &value, 0, 64)?),
+            "--samples" => samples = number(&name, &value, 1, 4096)?,
+            "--pattern" => {
+                pattern = Pattern::from_name(&value)
+                    .ok_or_else(|| format!("{}: unknown pattern `{}`", name, value))?;
+            }
+            "--filter" => {
+                filter = Filter::from_name(&value)
+                    .ok_or_else(|| format!("{}: unknown filter `{}`", name, value))?;
+            }
+            "--adaptive" => {
+                adaptive = Some(value.parse().ok().filter(|t: &f32| (0.0..=1.0).contains(t))
+                    .and_then(Adaptive::new)
+                    .ok_or_else(|| format!("{}: must be a number between 0 and 1, got `{}`", name, value))?);
+            }
+            "--threads" => threads = number(&name, &value, 1, 1024)? as usize,
+            "--exposure" => {
+                tone_map.exposure = value.parse().ok().filter(|e: &f32| e.is_finite())
+                    .ok_or_else(|| format!("{}: `{}` is not a number", name, value))?;
+            }
+            "--tonemap" => {
+                tone_map.operator = Operator::from_name(&value)
+                    .ok_or_else(|| format!("{}: unknown operator `{}`", name, value))?;
+            }
+            "--white" => {
+                white = Some(value.parse().ok().filter(|w: &f32| *w > 0.0)
+                    .ok_or_else(|| format!("{}: must be a positive number, got `{}`", name, value))?);
+            }
+            "--transfer" => {
+                tone_map.transfer = Transfer::from_name(&value)
+                    .ok_or_else(|| format!("{}: unknown transfer function `{}`", name, value))?;
+            }
+            _ => {
+                format = Some(ImageFormat::from_name(&value)
+                    .ok_or_else(|| format!("{}: unknown format `{}`", name, value))?);
+            }
+        }
+    }
+
+    let format = match (format, &output) {
+        (Some(f), _) => f,
+        (None, Some(path)) if path != "-" && Path::new(path).extension().is_some() => {
+            ImageFormat::from_path(path)
+                .ok_or_else(|| format!("cannot guess the format of `{}`, use --format", path))?
+        }
+        (None, _) => ImageFormat::Ppm,
+    };
+
+    let side = (samples as f32).sqrt().round() as u32;
+    if pattern.needs_square_count() && side * side != samples {
+        return Err(format!("--samples: grid and jittered patterns need a square number (1, 4, 9, ...), got {}",
+            samples));
+    }
+
+    if let Some(a) = adaptive {
+        if samples <= a.initial {
+            return Err(format!("--adaptive: needs --samples above the {} rays of the first pass, got {}",
+                a.initial, samples));
+        }
+    }
+
+    if let Some(w) = white {
+        match tone_map.operator {
+            Operator::Reinhard { ref mut white } => *white = w,
+            _ => return Err("--white only makes sense with --tonemap reinhard".to_string()),
+        }
+    }
+
+    Ok(Command::Render(Options {
+        scene: scene.unwrap_or_else(|| "scenes/default.toml".to_string()),
+        output,
+        no_clobber,
+        width,
+        height,
+        fov,
+        depth,
+        samples,
+        pattern,
+        filter,
+        adaptive,
+        threads,
+        format,
+        tone_map,
+    }))
+}
+
+fn number(name: &str, value: &str, min: u32, max: u32) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n >= min && n <= max => Ok(n),
+        Ok(_) => Err(format!("{}: must be in {}..={}, got {}", name, min, max, value)),
+        Err(_) => Err(format!("{}: `{}` is not a whole number", name, value)),
+    }
+}
@@ -0,0 +1,183 @@
+// Floating point colors and the linear framebuffer the tracer renders into.
+//
+// Nothing is clamped here : values above 1.0 are kept until the picture is
+// either stored as Radiance HDR or turned into an 8 bit PPM.
+
+use std::io::{self, Write};
+use std::ops::{Add, AddAssign, Mul};
+use std::path::{Path, PathBuf};
+
+use crate::PPM::{ImageFormat, Overwrite, PPM, RGB};
+use crate::tonemap::ToneMap;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct RGBf {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl RGBf {
+    pub fn new(rr: f32, gg: f32, bb: f32) -> RGBf {
+        RGBf {
+            r: rr,
+            g: gg,
+            b: bb,
+        }
+    }
+
+    // 255 maps to 1.0
+    pub fn from_rgb(c: RGB) -> RGBf {
+        RGBf::new(c.r as f32 / 255.0, c.g as f32 / 255.0, c.b as f32 / 255.0)
+    }
+
+    // plain clamp, everything above 1.0 becomes 255
+    pub fn to_rgb(self) -> RGB {
+        let q = |v: f32| (v * 255.0).floor().clamp(0.0, 255.0) as u8;
+        RGB::new(q(self.r), q(self.g), q(self.b))
+    }
+
+    pub fn scale(self, k: f32) -> RGBf {
+        RGBf::new(self.r * k, self.g * k, self.b * k)
+    }
+
+    pub fn max_channel(self) -> f32 {
+        self.r.max(self.g).max(self.b)
+    }
+}
+
+impl Add for RGBf {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        RGBf::new(self.r + other.r, self.g + other.g, self.b + other.b)
+    }
+}
+
+impl AddAssign for RGBf {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+// channel by channel, a colored light on a colored surface
+impl Mul for RGBf {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        RGBf::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
+}
+
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    data: Vec<RGBf>,
+}
+
+impl Framebuffer {
+    pub fn new(w: u32, h: u32) -> Framebuffer {
+        Framebuffer {
+            width: w,
+            height: h,
+            data: vec![RGBf::default(); w as usize * h as usize],
+        }
+    }
+
+    pub fn from_ppm(img: &PPM) -> Framebuffer {
+        let mut fb = Framebuffer::new(img.width(), img.height());
+        for y in 0..img.height() {
+            for x in 0..img.width() {
+                fb.set_pixel(x, y, RGBf::from_rgb(img.get_pixel(x, y).unwrap()));
+            }
+        }
+        fb
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, x: u32, y: u32) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y as usize * self.width as usize + x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: RGBf) -> bool {
+        match self.index(x, y) {
+            Some(i) => {
+                self.data[i] = color;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<RGBf> {
+        self.index(x, y).map(|i| self.data[i])
+    }
+
+    pub fn pixels(&self) -> &[RGBf] {
+        &self.data
+    }
+
+    // the final step for 8 bit outputs
+    pub fn tone_map(&self, tm: &ToneMap) -> PPM {
+        let mut img = PPM::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                img.set_pixel(x, y, tm.to_rgb(self.data[self.index(x, y).unwrap()]));
+            }
+        }
+        img
+    }
+
+    pub fn to_ppm(&self) -> PPM {
+        self.tone_map(&ToneMap::default())
+    }
+
+    // Radiance RGBE, written as flat (not run-length encoded) scanlines
+    pub fn write_hdr_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n")?;
+        w.write_all(format!("-Y {} +X {}\n", self.height, self.width).as_bytes())?;
+        for row in self.data.chunks(self.width.max(1) as usize) {
+            let line: Vec<u8> = row.iter().flat_map(|c| rgbe(*c)).collect();
+            w.write_all(&line)?;
+        }
+        w.flush()
+    }
+
+    pub fn write_as<W: Write>(&self, w: W, format: ImageFormat) -> io::Result<()> {
+        match format {
+            ImageFormat::Hdr => self.write_hdr_to(w),
+            _ => self.to_ppm().write_as(w, format),
+        }
+    }
+
+    pub fn save_as<P: AsRef<Path>>(&self, path: P, format: ImageFormat, policy: Overwrite) -> io::Result<()> {
+        self.write_as(crate::PPM::create_file(path, policy)?, format)
+    }
+
+    pub fn save_file(&self, format: ImageFormat) -> io::Result<PathBuf> {
+        crate::PPM::save_numbered(Path::new(""), format, |path| self.save_as(path, format, Overwrite::Deny))
+    }
+}
+
+// shared exponent encoding, see Greg Ward's "Real Pixels" in Graphics Gems II
+fn rgbe(c: RGBf) -> [u8; 4] {
+    let v = c.max_channel();
+    if v.is_nan() || v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // keeps the exponent byte in range
+    let v = v.min(1e38);
+    let e = v.log2().floor() as i32 + 1;
+    let k = 256.0 / 2f32.powi(e);
+    let q = |x: f32| (x.max(0.0) * k).min(255.0) as u8;
+    [q(c.r), q(c.g), q(c.b), (e + 128) as u8]
+}
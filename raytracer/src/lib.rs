@@ -1,3 +1,15 @@
+pub mod bvh;
+pub mod camera;
+pub mod hdr;
+pub mod obj;
+pub mod png;
+pub mod render;
+pub mod sampling;
+pub mod scene_file;
+pub mod shapes;
+pub mod tonemap;
+
+#[allow(non_snake_case)]
 pub mod III_vector {
     use std::ops::{Mul, Add, Sub, Div};
 
@@ -55,7 +67,7 @@ pub mod III_vector {
             Vec3::<T> {
                 x: a.y*b.z - a.z*b.y, 
                 y: a.z*b.x - a.x*b.z,
-                z: a.x*b.y - a.y*b.z,
+                z: a.x*b.y - a.y*b.x,
             }
         }
     }
@@ -90,6 +102,16 @@ pub mod III_vector {
         }
     }
 
+    impl Vec3<f32> {
+        // two unit vectors completing this unit vector into a right-handed
+        // basis (a, b, self)
+        pub fn orthonormal(self) -> (Vec3<f32>, Vec3<f32>) {
+            let side = if self.x.abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+            let a = Vec3::prod(side, self).norm();
+            (a, Vec3::prod(self, a))
+        }
+    }
+
     impl<T: Copy + Eq + num::Zero> num::Zero for Vec3<T> {
         fn zero() -> Self {
             Vec3::<T>::new(num::zero(), num::zero(), num::zero(),)
@@ -105,13 +127,211 @@ pub mod III_vector {
         pub root: Vec3<T>,
         pub dir: Vec3<T>,
     }
+
+    // m[row][column], acting on column vectors : the last column is the
+    // translation
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub struct Mat4 {
+        pub m: [[f32; 4]; 4],
+    }
+
+    impl Mat4 {
+        pub fn new(m: [[f32; 4]; 4]) -> Mat4 {
+            Mat4 { m }
+        }
+
+        pub fn identity() -> Mat4 {
+            Mat4::scaling(Vec3::new(1.0, 1.0, 1.0))
+        }
+
+        pub fn translation(t: Vec3<f32>) -> Mat4 {
+            let mut r = Mat4::identity();
+            r.m[0][3] = t.x;
+            r.m[1][3] = t.y;
+            r.m[2][3] = t.z;
+            r
+        }
+
+        pub fn scaling(s: Vec3<f32>) -> Mat4 {
+            Mat4::new([
+                [s.x, 0.0, 0.0, 0.0],
+                [0.0, s.y, 0.0, 0.0],
+                [0.0, 0.0, s.z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+        }
+
+        // counterclockwise when `axis` points to the viewer (Rodrigues)
+        pub fn rotation(axis: Vec3<f32>, degrees: f32) -> Mat4 {
+            let a = axis.norm();
+            let (sin, cos) = degrees.to_radians().sin_cos();
+            let k = 1.0 - cos;
+            Mat4::new([
+                [cos + a.x * a.x * k, a.x * a.y * k - a.z * sin, a.x * a.z * k + a.y * sin, 0.0],
+                [a.y * a.x * k + a.z * sin, cos + a.y * a.y * k, a.y * a.z * k - a.x * sin, 0.0],
+                [a.z * a.x * k - a.y * sin, a.z * a.y * k + a.x * sin, cos + a.z * a.z * k, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+        }
+
+        pub fn transpose(&self) -> Mat4 {
+            let mut r = *self;
+            for (i, row) in r.m.iter_mut().enumerate() {
+                for (j, x) in row.iter_mut().enumerate() {
+                    *x = self.m[j][i];
+                }
+            }
+            r
+        }
+
+        // Gauss-Jordan with partial pivoting ; None for a singular matrix
+        pub fn inverse(&self) -> Option<Mat4> {
+            let mut a = [[0f64; 8]; 4];
+            for (i, row) in a.iter_mut().enumerate() {
+                for (x, y) in row.iter_mut().zip(self.m[i].iter()) {
+                    *x = *y as f64;
+                }
+                row[4 + i] = 1.0;
+            }
+            for col in 0..4 {
+                let pivot = (col..4).max_by(|&x, &y| a[x][col].abs().total_cmp(&a[y][col].abs()))?;
+                let p = a[pivot][col];
+                if p.is_nan() || p.abs() <= 1e-12 {
+                    return None;
+                }
+                a.swap(col, pivot);
+                for x in a[col].iter_mut() {
+                    *x /= p;
+                }
+                for row in 0..4 {
+                    if row != col {
+                        let k = a[row][col];
+                        let pivot_row = a[col];
+                        for (x, y) in a[row].iter_mut().zip(pivot_row.iter()) {
+                            *x -= k * y;
+                        }
+                    }
+                }
+            }
+            let mut r = Mat4::identity();
+            for (row, src) in r.m.iter_mut().zip(a.iter()) {
+                for (x, y) in row.iter_mut().zip(src[4..].iter()) {
+                    *x = *y as f32;
+                }
+            }
+            Some(r)
+        }
+
+        // w = 1, the translation applies
+        pub fn point(&self, p: Vec3<f32>) -> Vec3<f32> {
+            self.dir(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+        }
+
+        // w = 0
+        pub fn dir(&self, d: Vec3<f32>) -> Vec3<f32> {
+            let row = |i: usize| Vec3::new(self.m[i][0], self.m[i][1], self.m[i][2]) * d;
+            Vec3::new(row(0), row(1), row(2))
+        }
+    }
+
+    // `a * b` applies b first
+    impl Mul for Mat4 {
+        type Output = Self;
+        fn mul(self, rhs: Self) -> Self {
+            let mut r = Mat4::new([[0.0; 4]; 4]);
+            for i in 0..4 {
+                for j in 0..4 {
+                    r.m[i][j] = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+                }
+            }
+            r
+        }
+    }
+
+    // an invertible matrix kept with its inverse, from object to world space
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub struct Transform {
+        m: Mat4,
+        inv: Mat4,
+    }
+
+    impl Transform {
+        pub fn new(m: Mat4) -> Option<Transform> {
+            let inv = m.inverse()?;
+            Some(Transform { m, inv })
+        }
+
+        pub fn identity() -> Transform {
+            Transform { m: Mat4::identity(), inv: Mat4::identity() }
+        }
+
+        pub fn translate(t: Vec3<f32>) -> Transform {
+            Transform { m: Mat4::translation(t), inv: Mat4::translation(t.scale(-1.0)) }
+        }
+
+        // None if a factor is zero
+        pub fn scale(s: Vec3<f32>) -> Option<Transform> {
+            Transform::new(Mat4::scaling(s))
+        }
+
+        // None for a zero axis
+        pub fn rotate(axis: Vec3<f32>, degrees: f32) -> Option<Transform> {
+            let n = axis.len();
+            if n.is_nan() || n <= 0.0 {
+                return None;
+            }
+            let m = Mat4::rotation(axis, degrees);
+            Some(Transform { m, inv: m.transpose() })
+        }
+
+        // `self` first, then `next`
+        pub fn then(self, next: Transform) -> Transform {
+            Transform { m: next.m * self.m, inv: self.inv * next.inv }
+        }
+
+        pub fn inverse(self) -> Transform {
+            Transform { m: self.inv, inv: self.m }
+        }
+
+        pub fn matrix(&self) -> Mat4 {
+            self.m
+        }
+
+        pub fn point(&self, p: Vec3<f32>) -> Vec3<f32> {
+            self.m.point(p)
+        }
+
+        pub fn dir(&self, d: Vec3<f32>) -> Vec3<f32> {
+            self.m.dir(d)
+        }
+
+        // normals follow the inverse transpose to stay orthogonal to the
+        // surface under non-uniform scaling ; the result is a unit vector
+        pub fn normal(&self, n: Vec3<f32>) -> Vec3<f32> {
+            self.inv.transpose().dir(n).norm()
+        }
+
+        pub fn inv_point(&self, p: Vec3<f32>) -> Vec3<f32> {
+            self.inv.point(p)
+        }
+
+        pub fn inv_dir(&self, d: Vec3<f32>) -> Vec3<f32> {
+            self.inv.dir(d)
+        }
+
+        pub fn inv_normal(&self, n: Vec3<f32>) -> Vec3<f32> {
+            self.m.transpose().dir(n).norm()
+        }
+    }
 }
 
+#[allow(non_snake_case)]
 pub mod PPM {
-    use std::io::Write;
-    use std::fs::OpenOptions;
+    use std::io::{self, BufWriter, Read, Write};
+    use std::fs::{File, OpenOptions};
+    use std::path::{Path, PathBuf};
 
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
     pub struct RGB {
         pub r: u8,
         pub g: u8,
@@ -128,31 +348,75 @@ pub mod PPM {
         }
     }
  
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub enum ImageFormat {
+        Ppm,
+        Png,
+        Hdr,
+    }
+
+    impl ImageFormat {
+        pub fn from_name(name: &str) -> Option<ImageFormat> {
+            match name.to_ascii_lowercase().as_str() {
+                "ppm" => Some(ImageFormat::Ppm),
+                "png" => Some(ImageFormat::Png),
+                "hdr" => Some(ImageFormat::Hdr),
+                _ => None,
+            }
+        }
+
+        // guesses the format from the extension of the file
+        pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ImageFormat> {
+            path.as_ref().extension()
+                .and_then(|e| e.to_str())
+                .and_then(ImageFormat::from_name)
+        }
+
+        pub fn extension(self) -> &'static str {
+            match self {
+                ImageFormat::Ppm => "ppm",
+                ImageFormat::Png => "png",
+                ImageFormat::Hdr => "hdr",
+            }
+        }
+    }
+
+    // what to do when the file to save into already exists
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub enum Overwrite {
+        Allow,
+        Deny,
+    }
+
+    // pixels are stored row by row, top to bottom, (x, y) = (column, row)
     pub struct PPM {
-        height: u32,
         width: u32,
+        height: u32,
         data: Vec<u8>,
     }
  
     impl PPM {
-        pub fn new(h: u32, w: u32) -> PPM {
-            let size = 3 * h * w;
-            let buf = vec![0; size as usize];
+        pub fn new(w: u32, h: u32) -> PPM {
+            let size = 3 * w as usize * h as usize;
+            let buf = vec![0; size];
             PPM { 
-                height: h,
                 width: w,
+                height: h,
                 data: buf,
             }
         }
- 
-        fn buffer_size(&self) -> u32 {
-            3 * self.height * self.width
+
+        pub fn width(&self) -> u32 {
+            self.width
+        }
+
+        pub fn height(&self) -> u32 {
+            self.height
         }
  
         fn get_offset(&self, x: u32, y: u32) -> Option<usize> {
-            let offset = (y * self.width * 3) + (x * 3);
-            if offset < self.buffer_size() {
-                Some(offset as usize)
+            if x < self.width && y < self.height {
+                Some((y as usize * self.width as usize + x as usize) * 3)
             } else {
                 None
             }
@@ -170,34 +434,146 @@ pub mod PPM {
             }
         }
 
-        pub fn save_file(&mut self) {
-            let mut picture_number = 0;
-            let mut file = loop {
-                if let Ok(i) = OpenOptions::new()
-                    .write(true)
-                    .create_new(true)
-                    .open(format!("Picture{}.ppm", picture_number)) {
-                        println!("Output will be stored in \"Picture{}\".ppm",
-                            picture_number);
-                        break i
-                } else {
-                    picture_number += 1;
+        pub fn get_pixel(&self, x: u32, y: u32) -> Option<RGB> {
+            self.get_offset(x, y)
+                .map(|offset| RGB::new(self.data[offset], self.data[offset + 1], self.data[offset + 2]))
+        }
+
+        // reads back a binary ppm with 8 bits per channel, as written by write_to()
+        pub fn read_from<R: Read>(mut r: R) -> io::Result<PPM> {
+            let mut fields = Vec::<u32>::new();
+            let mut magic = [0u8; 2];
+            for m in magic.iter_mut() {
+                *m = next_byte(&mut r)?;
+            }
+            if &magic != b"P6" {
+                return Err(invalid("not a binary ppm (P6) file"));
+            }
+
+            // width, height and max value, separated by whitespace and comments
+            let mut c = next_byte(&mut r)?;
+            while fields.len() < 3 {
+                if c == b'#' {
+                    while c != b'\n' {
+                        c = next_byte(&mut r)?;
+                    }
+                } else if c.is_ascii_digit() {
+                    let mut n: u32 = 0;
+                    while c.is_ascii_digit() {
+                        n = n.checked_mul(10)
+                            .and_then(|n| n.checked_add((c - b'0') as u32))
+                            .ok_or_else(|| invalid("number in ppm header is too large"))?;
+                        c = next_byte(&mut r)?;
+                    }
+                    if !c.is_ascii_whitespace() {
+                        return Err(invalid("malformed ppm header"));
+                    }
+                    fields.push(n);
+                    if fields.len() == 3 {
+                        break;
+                    }
+                } else if !c.is_ascii_whitespace() {
+                    return Err(invalid("malformed ppm header"));
                 }
-            };
-    
-            file.write(format!("P6 {} {} 255\n", self.height, self.width).as_bytes()).unwrap();
-            if let Ok(a) = file.write(&self.data) {
-                println!("Done --- {}", a);
-            } else {
-                println!("Ooops...");
+                c = next_byte(&mut r)?;
+            }
+            if fields[2] != 255 {
+                return Err(invalid("only 8 bit ppm files are supported"));
             }
+
+            let mut img = PPM::new(fields[0], fields[1]);
+            r.read_exact(&mut img.data)?;
+            Ok(img)
+        }
+
+        // writes the binary ppm (header and pixels) into any sink : a file,
+        // a pipe, a buffer in memory
+        pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+            w.write_all(format!("P6 {} {} 255\n", self.width, self.height).as_bytes())?;
+            w.write_all(&self.data)?;
+            w.flush()
+        }
+
+        pub fn write_png_to<W: Write>(&self, w: W) -> io::Result<()> {
+            crate::png::write_png(w, self.width, self.height, &self.data)
+        }
+
+        pub fn write_as<W: Write>(&self, w: W, format: ImageFormat) -> io::Result<()> {
+            match format {
+                ImageFormat::Ppm => self.write_to(w),
+                ImageFormat::Png => self.write_png_to(w),
+                ImageFormat::Hdr => crate::hdr::Framebuffer::from_ppm(self).write_hdr_to(w),
+            }
+        }
+
+        // the format follows the extension of `path`, ppm if it is unknown
+        pub fn save_to<P: AsRef<Path>>(&self, path: P, policy: Overwrite) -> io::Result<()> {
+            let format = ImageFormat::from_path(&path).unwrap_or(ImageFormat::Ppm);
+            self.save_as(path, format, policy)
+        }
+
+        pub fn save_as<P: AsRef<Path>>(&self, path: P, format: ImageFormat, policy: Overwrite) -> io::Result<()> {
+            self.write_as(create_file(path, policy)?, format)
         }
+
+        // stores the picture in the first free "Picture{n}.ppm" (or .png, ...)
+        // of the current directory and returns its name
+        pub fn save_file(&self, format: ImageFormat) -> io::Result<PathBuf> {
+            self.save_file_in("", format)
+        }
+
+        // the same in `dir`, the path returned starts with it
+        pub fn save_file_in<P: AsRef<Path>>(&self, dir: P, format: ImageFormat) -> io::Result<PathBuf> {
+            save_numbered(dir.as_ref(), format, |path| self.save_as(path, format, Overwrite::Deny))
+        }
+    }
+
+    pub(crate) fn create_file<P: AsRef<Path>>(path: P, policy: Overwrite) -> io::Result<BufWriter<File>> {
+        let mut opts = OpenOptions::new();
+        match policy {
+            Overwrite::Allow => opts.write(true).create(true).truncate(true),
+            Overwrite::Deny => opts.write(true).create_new(true),
+        };
+        Ok(BufWriter::new(opts.open(path)?))
+    }
+
+    // tries "Picture0.ext", "Picture1.ext", ... in `dir` until `save` finds
+    // one that does not exist yet
+    pub(crate) fn save_numbered<F>(dir: &Path, format: ImageFormat, mut save: F) -> io::Result<PathBuf>
+        where F: FnMut(&Path) -> io::Result<()> {
+        let mut picture_number = 0;
+        loop {
+            let path = dir.join(format!("Picture{}.{}", picture_number, format.extension()));
+            match save(&path) {
+                Ok(()) => return Ok(path),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => picture_number += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn next_byte<R: Read>(r: &mut R) -> io::Result<u8> {
+        let mut b = [0u8; 1];
+        r.read_exact(&mut b)?;
+        Ok(b[0])
+    }
+
+    fn invalid(msg: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg)
     }
 }
 
+// the constructors predate the lints, they are left as written
+#[allow(clippy::needless_return)]
 pub mod objects {
+    use std::sync::OnceLock;
+
     use super::III_vector::{Vec3, Ray}; 
     use super::PPM::RGB;
+    use super::hdr::RGBf;
+    use super::bvh::{Aabb, Bvh};
+    use super::sampling::{concentric_disk, Pattern, Rng};
+    use super::shapes::quadratic_roots;
 
     #[derive(Clone, Copy)]
     pub enum SurfaceType{
@@ -225,12 +601,18 @@ pub mod objects {
                 surface_type: so,
             }
         }
+
+        pub fn surface_type(&self) -> SurfaceType {
+            self.surface_type
+        }
     }
 
     #[derive(Clone, Copy)]
     pub struct Sphere {
         center: Vec3<f32>,
         radius: f32,
+        // hits closer than this to the origin of a ray are ignored
+        t_min: f32,
         opt: SurfaceOptions,
     }
 
@@ -240,6 +622,7 @@ pub mod objects {
                 return Some(Sphere{
                     center: c,
                     radius: r,
+                    t_min: EPSILON,
                     opt: o,
                 })
             }
@@ -247,64 +630,503 @@ pub mod objects {
                 return None;
             }
         }
+
+        // for scenes much larger or smaller than a unit, where the default
+        // EPSILON is too strict or lets rays hit the surface they leave
+        pub fn with_t_min(mut self, t: f32) -> Sphere {
+            self.t_min = t;
+            self
+        }
     }
 
     impl Sphere {
+        // where the whole line through `root` meets the sphere, the nearer
+        // first and both measured along the unit `dir` ; they may be behind
+        // the origin
+        pub fn roots(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<(f32, f32)> {
+            let d = dir.norm();
+            let oc = root - self.center;
+            let b = oc * d;
+            let c = oc * oc - self.radius * self.radius;
+            // b^2 - c, measured from the point of the line nearest to the
+            // center so that a far origin does not cancel it out
+            let closest = oc - d.scale(b);
+            let disc = self.radius * self.radius - closest * closest;
+            quadratic_roots(1.0, 2.0 * b, c, 4.0 * disc)
+        }
+
+        // the nearest hit farther than `t_min`, from outside or from inside
+        pub fn hit_after(&self, root: Vec3<f32>, dir: Vec3<f32>, t_min: f32) -> Option<f32> {
+            let (t0, t1) = self.roots(root, dir)?;
+            if t0 > t_min {
+                Some(t0)
+            } else if t1 > t_min {
+                Some(t1)
+            } else {
+                None
+            }
+        }
+
         pub fn ray_intersect(self, root: Vec3<f32>, dir: Vec3<f32> ) -> Option<f32> {
-            let new_sphere_center = self.center - root; 
-            let norm_dir = dir.norm();
-            let scal = norm_dir * new_sphere_center;
-            let proj = norm_dir.scale(scal);
-            let h = new_sphere_center - proj;
+            self.hit_after(root, dir, self.t_min)
+        }
 
-            if new_sphere_center*new_sphere_center <= self.radius*self.radius || scal <= 0.0 {
-                return None;
+        // get the ray from the point on the sphere and returns the outgoing
+        // ray : coming from outside it is bent in, crosses the sphere and is
+        // bent out on the far side ; coming from inside it is only bent out.
+        // It is reflected where it cannot get through
+        pub fn dir_passed_transparent_sphere(&self, ray: Ray<f32> ) -> Option<Ray<f32>> {
+            let coeff = match self.opt.surface_type {
+                SurfaceType::Transparent(coeff) => coeff,
+                _ => return None,
+            };
+            let d = ray.dir.norm();
+            let outward = (ray.root - self.center).norm();
+
+            if outward * d >= 0.0 {
+                let n = outward.scale(-1.0);
+                let dir = refract(d, n, 1.0 / coeff).unwrap_or_else(|| reflect(d, n));
+                return Some(Ray{ root: ray.root, dir });
+            }
+
+            let inside = match refract(d, outward, coeff) {
+                Some(t) => t,
+                None => return Some(Ray{ root: ray.root, dir: reflect(d, outward) }),
+            };
+            // the ray starts on the sphere, so the near root is 0 and the far
+            // one the chord ; solving the quadratic again could miss a grazing
+            // ray to rounding
+            let far = (-2.0 * ((ray.root - self.center) * inside)).max(0.0);
+            let exit = ray.root + inside.scale(far);
+            let n = (self.center - exit).norm();
+            Some(Ray{
+                root: exit,
+                dir: refract(inside, n, 1.0 / coeff).unwrap_or_else(|| reflect(inside, n)),
+            })
+        }
+    }
+
+    // the unit `d` bent through a surface of unit normal `n` facing against
+    // it, `eta` being the ratio of the sines of the outgoing and incoming
+    // angles ; None for total internal reflection
+    fn refract(d: Vec3<f32>, n: Vec3<f32>, eta: f32) -> Option<Vec3<f32>> {
+        let cos_in = -(n * d);
+        let sin2_out = eta * eta * (1.0 - cos_in * cos_in);
+        if sin2_out >= 1.0 {
+            return None;
+        }
+        Some((d.scale(eta) + n.scale(eta * cos_in - (1.0 - sin2_out).sqrt())).norm())
+    }
+
+    fn reflect(d: Vec3<f32>, n: Vec3<f32>) -> Vec3<f32> {
+        d - n.scale(2.0 * (d * n))
+    }
+
+    // below this distance a hit is taken for the surface the ray starts from
+    pub(crate) const EPSILON: f32 = 1e-4;
+
+    // how far off the surface, on the side it is seen from, shadow rays start
+    const SHADOW_BIAS: f32 = 1e-3;
+
+    // what a ray found on a shape
+    #[derive(Clone, Copy)]
+    pub struct HitRecord {
+        // distance along the ray
+        pub t: f32,
+        pub point: Vec3<f32>,
+        // unit, turned against the ray
+        pub normal: Vec3<f32>,
+        // surface coordinates, their range depends on the shape
+        pub uv: (f32, f32),
+        pub material: SurfaceOptions,
+        // the ray came from the side the shape's own normal points to
+        pub front_face: bool,
+    }
+
+    impl HitRecord {
+        // `outward` is the unit normal of the shape, whatever side was hit
+        pub fn new(t: f32, p: Vec3<f32>, outward: Vec3<f32>, dir: Vec3<f32>, uv: (f32, f32), m: SurfaceOptions)
+            -> HitRecord {
+            let front_face = outward * dir <= 0.0;
+            HitRecord {
+                t,
+                point: p,
+                normal: if front_face { outward } else { outward.scale(-1.0) },
+                uv,
+                material: m,
+                front_face,
+            }
+        }
+    }
+
+    // anything a ray can hit ; the shading only talks to shapes through this
+    pub trait Shape: Send + Sync {
+        // the nearest hit in front of `root`, `t` measured along the unit `dir`
+        fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord>;
+
+        fn ray_intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<f32> {
+            self.intersect(root, dir).map(|h| h.t)
+        }
+
+        // a box around the shape for the scene's BVH ; None for unbounded
+        // shapes, which are tested by every ray
+        fn bounds(&self) -> Option<Aabb> {
+            None
+        }
+
+        // where a ray going along `dir` goes on after entering a transparent
+        // shape at `hit` ; by default shapes are thin sheets of glass and let
+        // it through unbent
+        fn transmit(&self, hit: &HitRecord, dir: Vec3<f32>) -> Option<Ray<f32>> {
+            match hit.material.surface_type {
+                SurfaceType::Transparent(_) => Some(Ray{ root: hit.point, dir }),
+                _ => None,
             }
+        }
+    }
+
+    impl Shape for Sphere {
+        fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+            let t = Sphere::ray_intersect(*self, root, dir)?;
+            let p = root + dir.norm().scale(t);
+            let n = (p - self.center).norm();
+            // longitude around +z and latitude from the north pole
+            let uv = (0.5 + n.y.atan2(n.x) / (2.0 * std::f32::consts::PI), n.z.clamp(-1.0, 1.0).acos() / std::f32::consts::PI);
+            Some(HitRecord::new(t, p, n, dir.norm(), uv, self.opt))
+        }
 
-            if h*h <= self.radius * self.radius {
-                return Some(proj.len() - (self.radius*self.radius - h*h).sqrt());
+        fn ray_intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<f32> {
+            Sphere::ray_intersect(*self, root, dir)
+        }
+
+        fn bounds(&self) -> Option<Aabb> {
+            let r = Vec3::new(self.radius, self.radius, self.radius);
+            Some(Aabb::new(self.center - r, self.center + r))
+        }
+
+        fn transmit(&self, hit: &HitRecord, dir: Vec3<f32>) -> Option<Ray<f32>> {
+            self.dir_passed_transparent_sphere(Ray{ root: hit.point, dir })
+        }
+    }
+
+    // infinite, both sides look the same
+    #[derive(Clone, Copy)]
+    pub struct Plane {
+        point: Vec3<f32>,
+        normal: Vec3<f32>,
+        opt: SurfaceOptions,
+    }
+
+    impl Plane {
+        pub fn new(p: Vec3<f32>, n: Vec3<f32>, o: SurfaceOptions) -> Option<Plane> {
+            if n.len() > 0.0 {
+                Some(Plane{
+                    point: p,
+                    normal: n.norm(),
+                    opt: o,
+                })
             } else {
+                None
+            }
+        }
+
+        fn distance(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<f32> {
+            let facing = self.normal * dir;
+            if facing.abs() < 1e-8 {
                 return None;
             }
+            let t = self.normal * (self.point - root) / facing;
+            if t > EPSILON { Some(t) } else { None }
         }
+    }
 
-        // get the ray from the point on the sphere and returns the outgoing ray
-        pub fn dir_passed_transparent_sphere(&self, ray: Ray<f32> ) -> Option<Ray<f32>> {
-            if let SurfaceType::Transparent(coeff) = self.opt.surface_type {
-                let n_norm = (self.center - ray.root).norm();
-                let proj = n_norm * ray.dir.norm(); // it is also cos(\a)
-                let neg_dir = n_norm.scale(2.0*proj) - ray.dir.norm();
-                let sin_a = (1.0 - proj*proj).sqrt(); // suppose 0 < a < 90 
-                let sin_b = sin_a * coeff; // suppose 0 < b < 90
-                if sin_b >= 1.0 { // if cannot pass into, so reflect
-                    return Some(Ray{
-                        root: ray.root,
-                        dir: n_norm.scale(-2.0*proj) + ray.dir.norm(),
-                    });
+    impl Shape for Plane {
+        fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+            let dir = dir.norm();
+            let t = self.distance(root, dir)?;
+            let p = root + dir.scale(t);
+            // world units along two axes lying in the plane
+            let (a, b) = self.normal.orthonormal();
+            let uv = ((p - self.point) * a, (p - self.point) * b);
+            Some(HitRecord::new(t, p, self.normal, dir, uv, self.opt))
+        }
+    }
+
+    // the parallelogram spanned by the edges `u` and `v` from `corner`
+    #[derive(Clone, Copy)]
+    pub struct Quad {
+        corner: Vec3<f32>,
+        u: Vec3<f32>,
+        v: Vec3<f32>,
+        normal: Vec3<f32>,
+        opt: SurfaceOptions,
+    }
+
+    impl Quad {
+        pub fn new(c: Vec3<f32>, u: Vec3<f32>, v: Vec3<f32>, o: SurfaceOptions) -> Option<Quad> {
+            let n = Vec3::prod(u, v);
+            if n.len() > 0.0 {
+                Some(Quad{
+                    corner: c,
+                    u,
+                    v,
+                    normal: n.norm(),
+                    opt: o,
+                })
+            } else {
+                None
+            }
+        }
+    }
+
+    impl Shape for Quad {
+        fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+            let dir = dir.norm();
+            let t = Plane{ point: self.corner, normal: self.normal, opt: self.opt }.distance(root, dir)?;
+            // coordinates of the hit along the edges, both in [0, 1] inside
+            let p = root + dir.scale(t);
+            let q = p - self.corner;
+            let n = Vec3::prod(self.u, self.v);
+            let a = n * Vec3::prod(q, self.v) / (n * n);
+            let b = n * Vec3::prod(self.u, q) / (n * n);
+            if (0.0..=1.0).contains(&a) && (0.0..=1.0).contains(&b) {
+                Some(HitRecord::new(t, p, self.normal, dir, (a, b), self.opt))
+            } else {
+                None
+            }
+        }
+
+        fn bounds(&self) -> Option<Aabb> {
+            let c = self.corner;
+            Some(Aabb::from_points(&[c, c + self.u, c + self.v, c + self.u + self.v]))
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    pub struct Triangle {
+        a: Vec3<f32>,
+        b: Vec3<f32>,
+        c: Vec3<f32>,
+        opt: SurfaceOptions,
+    }
+
+    impl Triangle {
+        pub fn new(a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>, o: SurfaceOptions) -> Option<Triangle> {
+            if Vec3::prod(b - a, c - a).len() > 0.0 {
+                Some(Triangle{ a, b, c, opt: o })
+            } else {
+                None
+            }
+        }
+    }
+
+    impl Shape for Triangle {
+        // the uv are the barycentric coordinates of `b` and `c`
+        fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+            let dir = dir.norm();
+            let (t, u, w) = moller_trumbore([self.a, self.b, self.c], root, dir)?;
+            let n = Vec3::prod(self.b - self.a, self.c - self.a).norm();
+            Some(HitRecord::new(t, root + dir.scale(t), n, dir, (u, w), self.opt))
+        }
+
+        fn bounds(&self) -> Option<Aabb> {
+            Some(Aabb::from_points(&[self.a, self.b, self.c]))
+        }
+    }
+
+    // Moller-Trumbore : the distance along the unit `dir` and the barycentric
+    // coordinates of the hit relative to the 2nd and 3rd vertices
+    fn moller_trumbore(v: [Vec3<f32>; 3], root: Vec3<f32>, dir: Vec3<f32>) -> Option<(f32, f32, f32)> {
+        let e1 = v[1] - v[0];
+        let e2 = v[2] - v[0];
+        let p = Vec3::prod(dir, e2);
+        let det = e1 * p;
+        if det.abs() < 1e-10 {
+            return None;
+        }
+        let inv = 1.0 / det;
+        let s = root - v[0];
+        let u = s * p * inv;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = Vec3::prod(s, e1);
+        let w = dir * q * inv;
+        if w < 0.0 || u + w > 1.0 {
+            return None;
+        }
+        let t = e2 * q * inv;
+        if t > EPSILON { Some((t, u, w)) } else { None }
+    }
+
+    // triangles sharing an indexed vertex list ; the normals given per vertex
+    // are blended over every face for smooth shading
+    #[derive(Clone)]
+    pub struct TriangleMesh {
+        vertices: Vec<Vec3<f32>>,
+        normals: Vec<Vec3<f32>>,
+        // texture coordinates per vertex, empty if the mesh has none
+        uvs: Vec<(f32, f32)>,
+        faces: Vec<[usize; 3]>,
+        // over the faces
+        bvh: Bvh,
+        opt: SurfaceOptions,
+    }
+
+    impl TriangleMesh {
+        // without `normals` every vertex gets the mean of the faces around it,
+        // weighted by their area ; None if an index is out of range or the
+        // normals do not match the vertices
+        pub fn new(v: Vec<Vec3<f32>>, f: Vec<[usize; 3]>, n: Option<Vec<Vec3<f32>>>, o: SurfaceOptions)
+            -> Option<TriangleMesh> {
+            if f.is_empty() || f.iter().flatten().any(|&i| i >= v.len()) {
+                return None;
+            }
+            let normals = match n {
+                Some(n) => {
+                    if n.len() != v.len() || n.iter().any(|x| x.len().is_nan() || x.len() <= 0.0) {
+                        return None;
+                    }
+                    n.into_iter().map(|x| x.norm()).collect()
                 }
-                let cos_b = (1.0 - sin_b*sin_b).sqrt();
-                let tg_2b = (2.0 * sin_b * cos_b) / (cos_b*cos_b - sin_b*sin_b);
-                let dir_to_n = n_norm - neg_dir.scale(proj);
-                let outgoing_ray = neg_dir.scale(tg_2b.signum()) + dir_to_n.norm().scale(tg_2b.abs());
-                let n_to_ray = ray.dir.norm() - n_norm.scale(proj);
-                let outgoing_point = self.center + (n_norm.scale(tg_2b.signum()) +
-                    n_to_ray.norm().scale(tg_2b.abs())).norm().scale(self.radius);
-
-                return Some(Ray{
-                    root: outgoing_point,
-                    dir: outgoing_ray,
-                });
+                None => {
+                    let mut acc = vec![Vec3::new(0.0, 0.0, 0.0); v.len()];
+                    for t in f.iter() {
+                        // not normalized, so bigger faces count more
+                        let face = Vec3::prod(v[t[1]] - v[t[0]], v[t[2]] - v[t[0]]);
+                        for &i in t.iter() {
+                            acc[i] = acc[i] + face;
+                        }
+                    }
+                    acc.into_iter()
+                        .map(|x| if x.len() > 0.0 { x.norm() } else { x })
+                        .collect()
+                }
+            };
+            let boxes: Vec<Aabb> = f.iter()
+                .map(|t| Aabb::from_points(&[v[t[0]], v[t[1]], v[t[2]]]))
+                .collect();
+            Some(TriangleMesh {
+                vertices: v,
+                normals,
+                uvs: Vec::new(),
+                faces: f,
+                bvh: Bvh::new(&boxes),
+                opt: o,
+            })
+        }
+
+        // None unless there is one pair per vertex
+        pub fn with_uvs(mut self, uv: Vec<(f32, f32)>) -> Option<TriangleMesh> {
+            if uv.len() != self.vertices.len() {
+                return None;
+            }
+            self.uvs = uv;
+            Some(self)
+        }
+
+        pub fn with_opt(mut self, o: SurfaceOptions) -> TriangleMesh {
+            self.opt = o;
+            self
+        }
+
+        pub fn uvs(&self) -> &[(f32, f32)] {
+            &self.uvs
+        }
+
+        pub fn len(&self) -> usize {
+            self.faces.len()
+        }
+
+        // never true, a mesh is built with at least one face
+        pub fn is_empty(&self) -> bool {
+            self.faces.is_empty()
+        }
+    }
+
+    impl Shape for TriangleMesh {
+        // the uv come from the vertices if the mesh has them, otherwise they
+        // are the barycentric coordinates in the face that was hit
+        fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+            let dir = dir.norm();
+            let corners = |i: usize| {
+                let f = self.faces[i];
+                [self.vertices[f[0]], self.vertices[f[1]], self.vertices[f[2]]]
+            };
+            let (i, _) = self.bvh.nearest(root, dir, |i| moller_trumbore(corners(i), root, dir).map(|h| h.0))?;
+            let (t, u, w) = moller_trumbore(corners(i), root, dir)?;
+            let f = self.faces[i];
+            let blend = |a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>| a.scale(1.0 - u - w) + b.scale(u) + c.scale(w);
+            let n = blend(self.normals[f[0]], self.normals[f[1]], self.normals[f[2]]);
+            let n = if n.len() > 0.0 {
+                n.norm()
             } else {
+                let v = [self.vertices[f[0]], self.vertices[f[1]], self.vertices[f[2]]];
+                Vec3::prod(v[1] - v[0], v[2] - v[0]).norm()
+            };
+            let uv = if self.uvs.is_empty() {
+                (u, w)
+            } else {
+                let [a, b, c] = [self.uvs[f[0]], self.uvs[f[1]], self.uvs[f[2]]];
+                let k = 1.0 - u - w;
+                (k * a.0 + u * b.0 + w * c.0, k * a.1 + u * b.1 + w * c.1)
+            };
+            Some(HitRecord::new(t, root + dir.scale(t), n, dir, uv, self.opt))
+        }
+
+        fn bounds(&self) -> Option<Aabb> {
+            Some(self.bvh.bounds())
+        }
+    }
+
+    // how the light fades with the distance d to the shaded point
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub enum Falloff {
+        // the same at any distance, as if one unit away
+        None,
+        // 1 / d^2, as a physical point light ; the default
+        InverseSquare,
+        // 1 / (constant + linear d + quadratic d^2)
+        Attenuation(f32, f32, f32),
+    }
+
+    impl Falloff {
+        // None unless every term is non-negative and one of them is not 0
+        pub fn attenuation(c: f32, l: f32, q: f32) -> Option<Falloff> {
+            let terms = [c, l, q];
+            if terms.iter().any(|t| t.is_nan() || *t < 0.0) || terms.iter().all(|t| *t == 0.0) {
                 return None;
             }
+            Some(Falloff::Attenuation(c, l, q))
+        }
+
+        pub fn factor(&self, d: f32) -> f32 {
+            match *self {
+                Falloff::None => 1.0,
+                // closer than EPSILON the light would be blinding
+                Falloff::InverseSquare => 1.0 / d.max(EPSILON).powi(2),
+                Falloff::Attenuation(c, l, q) => 1.0 / (c + l * d + q * d * d).max(EPSILON),
+            }
         }
     }
 
+    // the light is measured in the units of the framebuffer, where 1.0 shows
+    // a white surface as white
     #[derive(Clone, Copy)]
     pub struct LightOptions {
+        // how much of the intensity goes to highlights and to the diffuse
+        // term, 1.0 for a plain light
         spec_ref: f32,
         diff_ref: f32,
+        // the light it adds everywhere, whatever the distance and the
+        // direction : 0.02 lights an ambient white surface to 0.02
         amb_ref: f32, 
+        // white by default, every channel of the shading is tinted by it
+        color: RGBf,
+        // radiant intensity : a white surface facing the light d units away
+        // gets intensity / d^2 of diffuse light with the default InverseSquare
+        intensity: f32,
+        falloff: Falloff,
     }
 
     impl LightOptions {
@@ -313,39 +1135,202 @@ pub mod objects {
                 spec_ref: sp,
                 diff_ref: df,
                 amb_ref : amb,
+                color: RGBf::new(1.0, 1.0, 1.0),
+                intensity: 1.0,
+                falloff: Falloff::InverseSquare,
             }
         }
+
+        pub fn with_color(mut self, c: RGBf) -> LightOptions {
+            self.color = c;
+            self
+        }
+
+        pub fn with_intensity(mut self, k: f32) -> LightOptions {
+            self.intensity = k;
+            self
+        }
+
+        pub fn with_falloff(mut self, f: Falloff) -> LightOptions {
+            self.falloff = f;
+            self
+        }
+
+        pub fn color(&self) -> RGBf {
+            self.color
+        }
+
+        pub fn intensity(&self) -> f32 {
+            self.intensity
+        }
+
+        pub fn falloff(&self) -> Falloff {
+            self.falloff
+        }
+    }
+
+    // what emits the light of a Light ; area lights shine from both sides
+    #[derive(Clone, Copy)]
+    pub enum LightShape {
+        Point,
+        // a ball of this radius around the center
+        Sphere(f32),
+        // the parallelogram center + a u + b v, a and b in [-0.5, 0.5]
+        Rect(Vec3<f32>, Vec3<f32>),
+        // facing the unit normal, of this radius
+        Disk(Vec3<f32>, f32),
     }
 
     #[derive(Clone, Copy)]
     pub struct Light {
        center: Vec3<f32>,
+       shape: LightShape,
+       // shadow rays per shaded point, spread over the shape
+       samples: u32,
        opt: LightOptions,
     }
 
+    // shadow rays of an area light unless told otherwise
+    pub const AREA_LIGHT_SAMPLES: u32 = 16;
+
     impl Light {
         pub fn new(c: Vec3<f32>, o: LightOptions) -> Light{
             Light {
                 center: c,
+                shape: LightShape::Point,
+                samples: 1,
                 opt: o,
             }
         }
+
+        pub fn sphere(c: Vec3<f32>, r: f32, o: LightOptions) -> Option<Light> {
+            if r.is_nan() || r <= 0.0 {
+                return None;
+            }
+            Some(Light::area(c, LightShape::Sphere(r), o))
+        }
+
+        // None if the edges are zero or parallel
+        pub fn rect(c: Vec3<f32>, u: Vec3<f32>, v: Vec3<f32>, o: LightOptions) -> Option<Light> {
+            let area = Vec3::prod(u, v).len();
+            if area.is_nan() || area <= 0.0 {
+                return None;
+            }
+            Some(Light::area(c, LightShape::Rect(u, v), o))
+        }
+
+        pub fn disk(c: Vec3<f32>, n: Vec3<f32>, r: f32, o: LightOptions) -> Option<Light> {
+            let len = n.len();
+            if len.is_nan() || len <= 0.0 || r.is_nan() || r <= 0.0 {
+                return None;
+            }
+            Some(Light::area(c, LightShape::Disk(n.norm(), r), o))
+        }
+
+        fn area(c: Vec3<f32>, shape: LightShape, o: LightOptions) -> Light {
+            Light {
+                center: c,
+                shape,
+                samples: AREA_LIGHT_SAMPLES,
+                opt: o,
+            }
+        }
+
+        // more samples give smoother penumbrae ; a point light needs one
+        pub fn with_samples(mut self, n: u32) -> Light {
+            self.samples = n.max(1);
+            self
+        }
+
+        pub fn samples(&self) -> u32 {
+            match self.shape {
+                LightShape::Point => 1,
+                _ => self.samples,
+            }
+        }
+
+        pub fn shape(&self) -> LightShape {
+            self.shape
+        }
+
+        pub fn options(&self) -> LightOptions {
+            self.opt
+        }
+
+        // where the shadow rays aimed from `p` end, `seed` scrambling their
+        // pattern so that neighbouring points do not share the same banding
+        pub fn sample_points(&self, p: Vec3<f32>, seed: u64) -> Vec<Vec3<f32>> {
+            if let LightShape::Point = self.shape {
+                return vec![self.center];
+            }
+            let mut rng = Rng::new(seed);
+            rng.next_u64();
+            let n = self.samples();
+            Pattern::Sobol.samples(n, rng)
+                .map(|(a, b)| match self.shape {
+                    LightShape::Point => self.center,
+                    LightShape::Rect(u, v) => self.center + u.scale(a - 0.5) + v.scale(b - 0.5),
+                    LightShape::Disk(normal, r) => self.center + disk_point(normal, r, a, b),
+                    // its outline as seen from `p`, a disk facing it
+                    LightShape::Sphere(r) => {
+                        let to_p = p - self.center;
+                        let facing = if to_p.len() > r { to_p.norm() } else { Vec3::new(0.0, 0.0, 1.0) };
+                        self.center + disk_point(facing, r, a, b)
+                    }
+                })
+                .collect()
+        }
+    }
+
+    // the point (a, b) of the unit square on a disk around the origin
+    fn disk_point(normal: Vec3<f32>, r: f32, a: f32, b: f32) -> Vec3<f32> {
+        let (x, y) = normal.orthonormal();
+        let (dx, dy) = concentric_disk(a, b);
+        x.scale(dx * r) + y.scale(dy * r)
     }
 
     pub struct Scene {
-        obj: Vec<Sphere>,
+        obj: Vec<Box<dyn Shape>>,
         lights: Vec<Light>,
+        max_depth: u32,
+        // built by the first ray after the objects changed
+        accel: OnceLock<Accel>,
+    }
+
+    // the BVH over the shapes that have bounds, the others are tested one
+    // by one
+    struct Accel {
+        bvh: Bvh,
+        // the index in `obj` of every item of the BVH
+        bounded: Vec<usize>,
+        unbounded: Vec<usize>,
+    }
+
+    impl Default for Scene {
+        fn default() -> Self {
+            Self::new()
+        }
     }
 
     impl Scene {
         pub fn new() -> Scene {
-            let v = Vec::<Sphere>::new();
+            let v = Vec::<Box<dyn Shape>>::new();
             let l = Vec::<Light>::new();
-            Scene{obj: v, lights: l}
+            Scene{obj: v, lights: l, max_depth: 4, accel: OnceLock::new()}
+        }
+
+        // how many mirror bounces and glass passes a ray may take
+        pub fn set_max_depth(&mut self, d: u32) {
+            self.max_depth = d;
         }
 
-        pub fn add_obj(&mut self, s: Sphere) {
-            self.obj.push(s);
+        pub fn max_depth(&self) -> u32 {
+            self.max_depth
+        }
+
+        pub fn add_obj<S: Shape + 'static>(&mut self, s: S) {
+            self.obj.push(Box::new(s));
+            self.accel = OnceLock::new();
         }
 
         pub fn add_light(&mut self, l: Light) {
@@ -354,208 +1339,194 @@ pub mod objects {
     }
 
     impl Scene {
-        pub fn ind_viewing(&self, root: Vec3<f32>, dir: Vec3<f32> ) -> Option<usize> {
-            if self.obj.is_empty(){ return None; }
-            let mut nearest_ind = 0 as usize;
-            let mut nearest_dist : Option<f32> = None;
+        fn accel(&self) -> &Accel {
+            self.accel.get_or_init(|| {
+                let mut boxes = Vec::new();
+                let (mut bounded, mut unbounded) = (Vec::new(), Vec::new());
+                for (ind, o) in self.obj.iter().enumerate() {
+                    match o.bounds() {
+                        Some(b) => {
+                            boxes.push(b);
+                            bounded.push(ind);
+                        }
+                        None => unbounded.push(ind),
+                    }
+                }
+                Accel { bvh: Bvh::new(&boxes), bounded, unbounded }
+            })
+        }
 
-            for ind in 0..self.obj.len() {
+        pub fn ind_viewing(&self, root: Vec3<f32>, dir: Vec3<f32> ) -> Option<usize> {
+            let dir = dir.norm();
+            let accel = self.accel();
+            let mut nearest = accel.bvh
+                .nearest(root, dir, |i| self.obj[accel.bounded[i]].ray_intersect(root, dir))
+                .map(|(i, d)| (accel.bounded[i], d));
+            for &ind in &accel.unbounded {
                 if let Some(d) = self.obj[ind].ray_intersect(root, dir) {
-                    if let Some(d_orig) = nearest_dist {
-                        if d < d_orig {
-                            nearest_ind = ind;
-                            nearest_dist = Some(d);
-                        }
-                    } else {
-                        nearest_ind = ind;
-                        nearest_dist = Some(d);
+                    if nearest.is_none_or(|(i, d_orig)| d < d_orig || (d == d_orig && ind < i)) {
+                        nearest = Some((ind, d));
                     }
                 }
             }
-                    
-            match nearest_dist {
-                Some(_) => Some(nearest_ind),
-                None => None,
+            nearest.map(|(ind, _)| ind)
+        }
+
+        // whether anything lies between `from` and `to` ; any hit will do, so
+        // the search stops at the first one
+        pub fn occluded(&self, from: Vec3<f32>, to: Vec3<f32>) -> bool {
+            let dist = (to - from).len();
+            if dist.is_nan() || dist <= 0.0 {
+                return false;
             }
+            let dir = (to - from).scale(1.0 / dist);
+            let accel = self.accel();
+            let hit = |ind: usize| self.obj[ind].ray_intersect(from, dir);
+            accel.unbounded.iter().any(|&ind| hit(ind).is_some_and(|t| t < dist))
+                || accel.bvh.any_hit(from, dir, dist, |i| hit(accel.bounded[i]))
+        }
+
+        // the index of the first object on the way and what the ray found there
+        fn nearest(&self, root: Vec3<f32>, dir: Vec3<f32> ) -> Option<(usize, HitRecord)> {
+            let ind = self.ind_viewing(root, dir)?;
+            self.obj[ind].intersect(root, dir).map(|h| (ind, h))
         }
     }
 
     impl Scene{
         pub fn color_on_dir(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<RGB> {
-            // find the object we're looking at 
-            if self.obj.is_empty(){ return None; }
-            let mut nearest_ind = 0 as usize;
-            let mut nearest_dist : Option<f32> = None;
+            self.radiance_on_dir(root, dir).map(RGBf::to_rgb)
+        }
 
-            for ind in 0..self.obj.len() {
-                if let Some(d) = self.obj[ind].ray_intersect(root, dir) {
-                    if let Some(d_orig) = nearest_dist {
-                        if d < d_orig {
-                            nearest_ind = ind;
-                            nearest_dist = Some(d);
-                        }
-                    } else {
-                        nearest_ind = ind;
-                        nearest_dist = Some(d);
-                    }
-                }
-            }
+        // linear, unclamped color : 1.0 is the brightest a ppm can hold, mirrors
+        // and highlights may go way above it
+        pub fn radiance_on_dir(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<RGBf> {
+            self.radiance_on_dir_depth(root, dir, self.max_depth)
+        }
+
+        fn radiance_on_dir_depth(&self, root: Vec3<f32>, dir: Vec3<f32>, depth: u32) -> Option<RGBf> {
+            // find the object we're looking at 
+            let (nearest_ind, hit) = self.nearest(root, dir)?;
 
             // handle founded object
-            let s: Sphere;
-            if let Some(dist) = nearest_dist {
-                s = self.obj[nearest_ind];
-                let mut p = root + dir.norm().scale(dist);
-                let mut bright: f32 = 0.0;
-
-                // evaluate the color of point on founded sphere
-                for ind in 0..self.lights.len() {
-                    let l = self.lights[ind];
-
-                    // ambient light
-                    bright += l.opt.amb_ref * s.opt.amb_ref;
-
-                    // check if there is an sphere obscuring the light
-                    if let Some(obstruct) = self.ind_viewing(l.center, p - l.center + (s.center - p).scale(0.001)) {
-                        // problem of accuracy : if the point located on the edge 
-                        // of visibility of some light origin then there is a chance,
-                        // that ind.vieving() will 'miss' and return as the aim-surface
-                        // the wrong one. 
-                        if obstruct != nearest_ind { 
-                            continue;
-                        }
+            let s = &self.obj[nearest_ind];
+            let opt = hit.material;
+            let mut p = hit.point;
+            // points into the object, against the side the ray came from
+            let inward = hit.normal.scale(-1.0);
+            // per channel, 1.0 showing the surface at its own color
+            let mut bright = RGBf::default();
+
+            // evaluate the color of point on founded object
+            for ind in 0..self.lights.len() {
+                let l = self.lights[ind];
+                let tint = l.opt.color.scale(l.opt.intensity);
+
+                // ambient light, it does not fade with the distance
+                bright += l.opt.color.scale(l.opt.amb_ref * opt.amb_ref);
+
+                // every point of the light seen from the shaded one adds its
+                // share, the hidden ones give the penumbra
+                let seed = (p.x.to_bits() as u64) ^ ((p.y.to_bits() as u64) << 21) ^ ((p.z.to_bits() as u64) << 42);
+                let points = l.sample_points(p, seed ^ ind as u64);
+                let share = 1.0 / points.len() as f32;
+                for c in points {
+                    // check if there is an object obscuring the light, the
+                    // shape itself included when the light is behind its surface
+                    if self.occluded(p + hit.normal.scale(SHADOW_BIAS), c) {
+                        continue;
                     }
 
-                    let scal = (p-l.center).norm() * (s.center - p).norm();
-                    if let SurfaceType::Solid(_) = s.opt.surface_type { // suppose the surface being ideal mirror or ideal linse
+                    let scal = (p-c).norm() * inward;
+                    let fade = l.opt.falloff.factor((c - p).len());
+                    if let SurfaceType::Solid(_) = opt.surface_type { // suppose the surface being ideal mirror or ideal linse
                         if scal > 0.0 {
-                            bright += l.opt.diff_ref * scal * s.opt.diff_ref;
+                            bright += tint.scale(l.opt.diff_ref * scal * opt.diff_ref * share * fade);
 
-                            debug_assert!(l.opt.diff_ref * scal * s.opt.diff_ref >= 0.0,
+                            debug_assert!(l.opt.diff_ref * scal * opt.diff_ref >= 0.0,
                                     "Diff light : \n\tl.diff_ref = {},\n\tscal = {},\n\ts.diff_ref = {}\nIn total gave {} < 0",
-                                    l.opt.diff_ref, scal, s.opt.diff_ref, l.opt.diff_ref * scal * s.opt.diff_ref);
+                                    l.opt.diff_ref, scal, opt.diff_ref, l.opt.diff_ref * scal * opt.diff_ref);
                         }
                     }
 
-                    let v_refl = (s.center-p).norm().scale(2.0*scal) - (p-l.center).norm();
+                    let v_refl = inward.scale(2.0*scal) - (p-c).norm();
                     let spec_proj = v_refl.norm() * dir.norm();
                     if spec_proj > 0.0 {
-                        bright += l.opt.spec_ref * spec_proj.powf(s.opt.shininess) * s.opt.spec_ref;
-                        debug_assert!(l.opt.spec_ref * spec_proj.powf(s.opt.shininess) * s.opt.spec_ref >= 0.0, 
+                        bright += tint.scale(l.opt.spec_ref * spec_proj.powf(opt.shininess) * opt.spec_ref * share * fade);
+                        debug_assert!(l.opt.spec_ref * spec_proj.powf(opt.shininess) * opt.spec_ref >= 0.0, 
                                 "Spec light:\n\tl.spec_ref = {},\n\tspec_proj = {},\n\ts.shininess = {},\n\ts.spec_ref ={}\nIn total gave {} < 0",
-                                l.opt.spec_ref, spec_proj, s.opt.shininess, s.opt.spec_ref, l.opt.spec_ref * spec_proj.powf(s.opt.shininess) * s.opt.spec_ref);
+                                l.opt.spec_ref, spec_proj, opt.shininess, opt.spec_ref, l.opt.spec_ref * spec_proj.powf(opt.shininess) * opt.spec_ref);
                     }
                 }
+            }
+
+            // init the foreground color 
+            let mut c = RGBf::from_rgb(RGB::new(10, 10, 10));
+
+            match opt.surface_type {
+                SurfaceType::Solid(s_color) => {
+                    c = RGBf::from_rgb(s_color) * bright;
+                }
 
-                // init the foreground color 
-                let mut r = 10;
-                let mut g = 10;
-                let mut b = 10;
-
-                match s.opt.surface_type {
-                    SurfaceType::Solid(s_color) => {
-                        r = (s_color.r as f32 * bright/255.0).floor() as u32;
-                        g = (s_color.g as f32 * bright/255.0).floor() as u32;
-                        b = (s_color.b as f32 * bright/255.0).floor() as u32;
-                        if r > 255 { r = 255; }
-                        if g > 255 { g = 255; }
-                        if b > 255 { b = 255; }
-                    }
+                SurfaceType::Mirror => {
+                    let norm = dir.norm() * inward;
+                    let mut next_dir = inward.scale(-2.0*norm) + dir.norm();
+                    let mut origin = true;
 
-                    SurfaceType::Mirror => {
-                        let mut norm = dir.norm() * (s.center-p).norm();
-                        let mut next_dir = (p-s.center).norm().scale(2.0*norm) + dir.norm();
-                        let mut origin = true;
-
-                        // let suppose the 4th reflection being miserably small
-                        for mut mirrors_on_the_way in 0..4{
-                            if let Some(next_ind) = self.ind_viewing(p, next_dir) {
-                                match self.obj[next_ind].opt.surface_type {
-                                    SurfaceType::Solid(_) => {
-                                        let seing = self.color_on_dir(p, next_dir).unwrap();
-                                        r = seing.r as u32;
-                                        g = seing.g as u32;
-                                        b = seing.b as u32;
-                                        break;
-                                    }
-
-                                    SurfaceType::Mirror => {
-                                        let next_sphere_center = self.obj[next_ind].center - p; 
-                                        let norm_dir = next_dir.norm();
-                                        let proj = norm_dir.scale(norm_dir * next_sphere_center);
-                                        let h = next_sphere_center - proj;
-                                        p = p + norm_dir.scale(proj.len() - (self.obj[next_ind].radius.powi(2) - h*h).sqrt());
-                                        norm = next_dir.norm() * (self.obj[next_ind].center - p).norm();
-                                        next_dir = (p - self.obj[next_ind].center).norm().scale(2.0*norm) + next_dir.norm();
-                                    }
-
-                                    SurfaceType::Transparent(_) => {
-                                        let next_sphere_center = self.obj[next_ind].center - p; 
-                                        let norm_dir = next_dir.norm();
-                                        let proj = norm_dir.scale(norm_dir * next_sphere_center);
-                                        let h = next_sphere_center - proj;
-                                        p = p + norm_dir.scale(proj.len() - (self.obj[next_ind].radius.powi(2) - h*h).sqrt());
-                                        let ray = self.obj[next_ind].dir_passed_transparent_sphere(Ray{
-                                            root: p,
-                                            dir : next_dir,
-                                        }).unwrap();
-                                        next_dir = ray.dir;
-                                        p = ray.root;
-                                        mirrors_on_the_way -= 1;
-                                    }
+                    // let suppose the reflection after max_depth being miserably small
+                    for _ in 0..depth {
+                        if let Some((next_ind, next_hit)) = self.nearest(p, next_dir) {
+                            match next_hit.material.surface_type {
+                                SurfaceType::Solid(_) => {
+                                    c = self.radiance_on_dir_depth(p, next_dir, 0).unwrap();
+                                    break;
                                 }
-                            } else {
-                                if origin {
-                                    // the infinity's color on the mirror and transparent surfaces
-                                    r = 5;
-                                    g = 5;
-                                    b = 5;
-                                } else {
-                                    // the color of infinity of reflrctions
-                                    r = 15;
-                                    g = 15;
-                                    b = 15;
+
+                                SurfaceType::Mirror => {
+                                    p = next_hit.point;
+                                    let n = next_hit.normal;
+                                    let norm = next_dir.norm() * n;
+                                    next_dir = next_dir.norm() - n.scale(2.0*norm);
                                 }
-                                break; 
+
+                                SurfaceType::Transparent(_) => {
+                                    let ray = self.obj[next_ind].transmit(&next_hit, next_dir)
+                                        .unwrap_or(Ray{ root: next_hit.point, dir: next_dir });
+                                    next_dir = ray.dir;
+                                    p = ray.root;
+                                }
+                            }
+                        } else {
+                            if origin {
+                                // the infinity's color on the mirror and transparent surfaces
+                                c = RGBf::from_rgb(RGB::new(5, 5, 5));
+                            } else {
+                                // the color of infinity of reflrctions
+                                c = RGBf::from_rgb(RGB::new(15, 15, 15));
                             }
-                            origin = false;
+                            break; 
                         }
-                        bright += 255.0;
-                        r = (r as f32 * bright/255.0).floor() as u32;
-                        g = (g as f32 * bright/255.0).floor() as u32;
-                        b = (b as f32 * bright/255.0).floor() as u32;
-                        if r > 255 { r = 255; }
-                        if g > 255 { g = 255; }
-                        if b > 255 { b = 255; }
+                        origin = false;
                     }
+                    bright += RGBf::new(1.0, 1.0, 1.0);
+                    c = c * bright;
+                }
 
-                    SurfaceType::Transparent(_) => {
-                        let ray = s.dir_passed_transparent_sphere(Ray{
-                            root: p,
-                            dir: dir }).unwrap();
-                        if let Some(color) = self.color_on_dir(ray.root, ray.dir) {
-                            r = color.r as u32;
-                            g = color.g as u32;
-                            b = color.b as u32;
-                        } else {
-                            r = 15;
-                            g = 15;
-                            b = 15;
-                        }
+                SurfaceType::Transparent(_) => {
+                    // a shape that cannot bend the ray lets it through
+                    let ray = s.transmit(&hit, dir).unwrap_or(Ray{ root: hit.point, dir });
+                    let seen = if depth > 0 {
+                        self.radiance_on_dir_depth(ray.root, ray.dir, depth - 1)
+                    } else {
+                        None
+                    };
+                    c = seen.unwrap_or_else(|| RGBf::from_rgb(RGB::new(15, 15, 15)));
 
-                        bright += 255.0;
-                        r = (r as f32 * bright/255.0).floor() as u32;
-                        g = (g as f32 * bright/255.0).floor() as u32;
-                        b = (b as f32 * bright/255.0).floor() as u32;
-                        if r > 255 { r = 255; }
-                        if g > 255 { g = 255; }
-                        if b > 255 { b = 255; }
-                    }
+                    bright += RGBf::new(1.0, 1.0, 1.0);
+                    c = c * bright;
                 }
-                return Some(RGB::new( r as u8, g as u8, b as u8));
-            } else {
-                return None;
             }
+            Some(c)
         }
     }
 }
@@ -0,0 +1,354 @@
+// Loader for Wavefront OBJ models and their MTL material libraries.
+//
+// Positions, texture coordinates, normals and faces are read, polygons are
+// cut into triangle fans (so they are expected to be convex) and the faces
+// are split into one mesh per group and material. Statements the tracer has
+// no use for (lines, points, smoothing groups, ...) are skipped.
+//
+// MTL materials are mapped onto the surfaces of the tracer : `Kd` is the
+// color, `Ks` the specular factor, `Ns` the shininess, and a dissolve `d`
+// below 1 (or `Tr` above 0) makes a transparent surface. Its coefficient is
+// 1 / `Ni`, as the tracer takes the ratio of the sines of the refracted and
+// incoming angles ; meshes are thin sheets of glass though and do not bend
+// the rays, only spheres use it.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::III_vector::Vec3;
+use crate::PPM::RGB;
+use crate::objects::{SurfaceOptions, SurfaceType, TriangleMesh};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, io::Error),
+    Parse { file: PathBuf, line: usize, msg: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(file, e) => write!(f, "{}: {}", file.display(), e),
+            ObjError::Parse { file, line, msg } => write!(f, "{}:{}: {}", file.display(), line, msg),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Material {
+    pub kd: [f32; 3],
+    pub ks: [f32; 3],
+    pub ns: f32,
+    pub ni: f32,
+    pub d: f32,
+}
+
+impl Default for Material {
+    // what faces without `usemtl` get : light gray, a little glossy
+    fn default() -> Self {
+        Material {
+            kd: [0.8, 0.8, 0.8],
+            ks: [0.0, 0.0, 0.0],
+            ns: 10.0,
+            ni: 1.0,
+            d: 1.0,
+        }
+    }
+}
+
+impl Material {
+    pub fn surface(&self) -> SurfaceOptions {
+        let spec = (self.ks[0] + self.ks[1] + self.ks[2]) / 3.0;
+        let kind = if self.d < 1.0 {
+            SurfaceType::Transparent(1.0 / self.ni)
+        } else {
+            let q = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+            SurfaceType::Solid(RGB::new(q(self.kd[0]), q(self.kd[1]), q(self.kd[2])))
+        };
+        SurfaceOptions::new(spec, 1.0, 1.0, self.ns, kind)
+    }
+}
+
+// the faces of one group drawn with one material
+pub struct ObjMesh {
+    pub group: String,
+    pub material: Option<String>,
+    pub mesh: TriangleMesh,
+}
+
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Vec<ObjMesh>, ObjError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|e| ObjError::Io(path.to_path_buf(), e))?;
+    parse_obj(&text, path)
+}
+
+// `file` names the model in errors, material libraries are looked for next
+// to it
+pub fn parse_obj(text: &str, file: &Path) -> Result<Vec<ObjMesh>, ObjError> {
+    let at = |line: usize, msg: String| ObjError::Parse { file: file.to_path_buf(), line, msg };
+    let dir = file.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut materials = HashMap::new();
+    let mut group = String::from("default");
+    let mut material: Option<String> = None;
+    let mut parts: Vec<Part> = Vec::new();
+
+    for (n, raw) in text.lines().enumerate() {
+        let line = n + 1;
+        let content = raw.split('#').next().unwrap().trim();
+        let mut words = content.split_whitespace();
+        let keyword = match words.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = numbers(&args, 3, 4).map_err(|e| at(line, format!("vertex: {}", e)))?;
+                positions.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                let [u, v, _] = numbers(&args, 1, 3).map_err(|e| at(line, format!("texture coordinate: {}", e)))?;
+                uvs.push((u, v));
+            }
+            "vn" => {
+                let [x, y, z] = numbers(&args, 3, 3).map_err(|e| at(line, format!("normal: {}", e)))?;
+                // tiny ones underflow to a zero length once squared
+                let n = Vec3::new(x, y, z);
+                let len = n.len();
+                if !len.is_finite() || len <= 0.0 {
+                    return Err(at(line, "normal: must not be zero".to_string()));
+                }
+                normals.push(n);
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(at(line, format!("a face needs at least 3 vertices, got {}", args.len())));
+                }
+                let mut corners = Vec::with_capacity(args.len());
+                for a in args.iter() {
+                    corners.push(corner(a, positions.len(), uvs.len(), normals.len()).map_err(|e| at(line, e))?);
+                }
+                let i = match parts.iter().position(|p| p.group == group && p.material == material) {
+                    Some(i) => i,
+                    None => {
+                        parts.push(Part::new(group.clone(), material.clone(), line));
+                        parts.len() - 1
+                    }
+                };
+                let part = &mut parts[i];
+                let ids: Vec<usize> = corners.iter()
+                    .map(|c| part.vertex(*c, &positions, &uvs, &normals))
+                    .collect();
+                for k in 1..ids.len() - 1 {
+                    part.faces.push([ids[0], ids[k], ids[k + 1]]);
+                }
+            }
+            "g" | "o" => {
+                group = if args.is_empty() { "default".to_string() } else { args.join(" ") };
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                if !materials.contains_key(&name) {
+                    return Err(at(line, format!("unknown material `{}`", name)));
+                }
+                material = Some(name);
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(at(line, "mtllib needs a file name".to_string()));
+                }
+                for lib in args.iter() {
+                    let path = dir.join(lib);
+                    let text = fs::read_to_string(&path).map_err(|e| ObjError::Io(path.clone(), e))?;
+                    materials.extend(parse_mtl(&text, &path)?);
+                }
+            }
+            // smoothing groups, lines, points, free form geometry...
+            _ => {}
+        }
+    }
+
+    let mut out = Vec::with_capacity(parts.len());
+    for p in parts {
+        let mat = p.material.as_ref().map_or_else(Material::default, |m| materials[m]);
+        let normals = if p.all_normals { Some(p.normals) } else { None };
+        let broken = at(p.line, format!("the faces of `{}` do not make a mesh", p.group));
+        let mesh = TriangleMesh::new(p.vertices, p.faces, normals, mat.surface()).ok_or(broken)?;
+        let mesh = if p.all_uvs { mesh.with_uvs(p.uvs).unwrap() } else { mesh };
+        out.push(ObjMesh {
+            group: p.group,
+            material: p.material,
+            mesh,
+        });
+    }
+    Ok(out)
+}
+
+pub fn parse_mtl(text: &str, file: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    let at = |line: usize, msg: String| ObjError::Parse { file: file.to_path_buf(), line, msg };
+    let mut out = HashMap::new();
+    let mut current: Option<(String, Material)> = None;
+
+    for (n, raw) in text.lines().enumerate() {
+        let line = n + 1;
+        let content = raw.split('#').next().unwrap().trim();
+        let mut words = content.split_whitespace();
+        let keyword = match words.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(at(line, "newmtl needs a name".to_string()));
+            }
+            if let Some((name, m)) = current.take() {
+                out.insert(name, m);
+            }
+            current = Some((args.join(" "), Material::default()));
+            continue;
+        }
+
+        let m = match current.as_mut() {
+            Some((_, m)) => m,
+            None if ["Kd", "Ks", "Ns", "Ni", "d", "Tr"].contains(&keyword) => {
+                return Err(at(line, format!("`{}` before any newmtl", keyword)));
+            }
+            None => continue,
+        };
+        let bad = |e: String| at(line, format!("{}: {}", keyword, e));
+        match keyword {
+            "Kd" => m.kd = color(&args).map_err(bad)?,
+            "Ks" => m.ks = color(&args).map_err(bad)?,
+            "Ns" => m.ns = numbers(&args, 1, 1).map_err(bad)?[0].max(0.0),
+            "Ni" => {
+                let ni = numbers(&args, 1, 1).map_err(bad)?[0];
+                if ni <= 0.0 {
+                    return Err(bad(format!("must be positive, got {}", ni)));
+                }
+                m.ni = ni;
+            }
+            "d" => m.d = numbers(&args, 1, 1).map_err(bad)?[0].clamp(0.0, 1.0),
+            "Tr" => m.d = 1.0 - numbers(&args, 1, 1).map_err(bad)?[0].clamp(0.0, 1.0),
+            // ambient, emission, illumination model, texture maps...
+            _ => {}
+        }
+    }
+    if let Some((name, m)) = current {
+        out.insert(name, m);
+    }
+    Ok(out)
+}
+
+// between `min` and `max` numbers, the missing ones up to 3 are zero
+fn numbers(args: &[&str], min: usize, max: usize) -> Result<[f32; 3], String> {
+    if args.len() < min || args.len() > max {
+        return Err(if min == max {
+            format!("expected {} numbers, got {}", min, args.len())
+        } else {
+            format!("expected {} to {} numbers, got {}", min, max, args.len())
+        });
+    }
+    let mut out = [0.0; 3];
+    for (i, a) in args.iter().enumerate().take(3) {
+        out[i] = a.parse::<f32>().ok().filter(|v| v.is_finite())
+            .ok_or_else(|| format!("`{}` is not a number", a))?;
+    }
+    Ok(out)
+}
+
+// `Kd 0.5` is a shade of gray
+fn color(args: &[&str]) -> Result<[f32; 3], String> {
+    if args.first() == Some(&"spectral") || args.first() == Some(&"xyz") {
+        return Err("only rgb colors are supported".to_string());
+    }
+    let c = numbers(args, 1, 3)?;
+    Ok(if args.len() == 1 { [c[0], c[0], c[0]] } else { c })
+}
+
+// 0 based position, uv and normal indices of a face corner
+type Corner = (usize, Option<usize>, Option<usize>);
+
+// `v`, `v/vt`, `v//vn` or `v/vt/vn`, 1 based or negative from the end
+fn corner(word: &str, nv: usize, nt: usize, nn: usize) -> Result<Corner, String> {
+    let index = |s: &str, count: usize, what: &str| -> Result<usize, String> {
+        let i: i64 = s.parse().map_err(|_| format!("bad {} index `{}` in `{}`", what, s, word))?;
+        let k = if i > 0 { i - 1 } else { count as i64 + i };
+        if i == 0 || k < 0 || k >= count as i64 {
+            return Err(format!("{} index {} out of range, there are {}", what, i, count));
+        }
+        Ok(k as usize)
+    };
+
+    let mut fields = word.split('/');
+    let v = index(fields.next().unwrap(), nv, "vertex")?;
+    let t = match fields.next() {
+        None | Some("") => None,
+        Some(s) => Some(index(s, nt, "texture")?),
+    };
+    let n = match fields.next() {
+        None | Some("") => None,
+        Some(s) => Some(index(s, nn, "normal")?),
+    };
+    if fields.next().is_some() {
+        return Err(format!("bad face corner `{}`", word));
+    }
+    Ok((v, t, n))
+}
+
+// a mesh being built : every distinct corner becomes one vertex
+struct Part {
+    group: String,
+    material: Option<String>,
+    // where its first face is
+    line: usize,
+    ids: HashMap<Corner, usize>,
+    vertices: Vec<Vec3<f32>>,
+    uvs: Vec<(f32, f32)>,
+    normals: Vec<Vec3<f32>>,
+    faces: Vec<[usize; 3]>,
+    all_uvs: bool,
+    all_normals: bool,
+}
+
+impl Part {
+    fn new(group: String, material: Option<String>, line: usize) -> Part {
+        Part {
+            group,
+            material,
+            line,
+            ids: HashMap::new(),
+            vertices: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            faces: Vec::new(),
+            all_uvs: true,
+            all_normals: true,
+        }
+    }
+
+    fn vertex(&mut self, c: Corner, positions: &[Vec3<f32>], uvs: &[(f32, f32)], normals: &[Vec3<f32>]) -> usize {
+        if let Some(&i) = self.ids.get(&c) {
+            return i;
+        }
+        let (v, t, n) = c;
+        self.vertices.push(positions[v]);
+        self.uvs.push(t.map_or((0.0, 0.0), |t| uvs[t]));
+        self.normals.push(n.map_or(Vec3::new(0.0, 0.0, 0.0), |n| normals[n]));
+        self.all_uvs &= t.is_some();
+        self.all_normals &= n.is_some();
+        self.ids.insert(c, self.vertices.len() - 1);
+        self.vertices.len() - 1
+    }
+}
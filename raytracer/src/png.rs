@@ -0,0 +1,270 @@
+// Self-contained PNG encoder for 8 bit RGB pictures.
+//
+// Every scanline gets the adaptive filter that minimizes the sum of absolute
+// differences, then the whole stream is compressed by a greedy LZ77 matcher
+// into a single deflate block with the fixed Huffman codes (RFC 1951 3.2.6),
+// wrapped in zlib (RFC 1950) and split into IDAT chunks.
+
+use std::io::{self, Write};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const IDAT_SIZE: usize = 1 << 16;
+
+// `rgb` holds width * height pixels row by row, three bytes each
+pub fn write_png<W: Write>(mut w: W, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    if width == 0 || height == 0 || rgb.len() != 3 * width as usize * height as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "pixel buffer does not match the picture size"));
+    }
+
+    w.write_all(&SIGNATURE)?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, truecolor, deflate, adaptive filtering, no interlace
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(&mut w, b"IHDR", &ihdr)?;
+
+    let stream = zlib(&filter(rgb, 3 * width as usize));
+    for part in stream.chunks(IDAT_SIZE) {
+        write_chunk(&mut w, b"IDAT", part)?;
+    }
+    write_chunk(&mut w, b"IEND", &[])?;
+    w.flush()
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let crc = crc32_update(crc32_update(!0, kind), data);
+    w.write_all(&(!crc).to_be_bytes())
+}
+
+// the checksum of PNG chunks (ISO 3309)
+pub fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0, data)
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+// the checksum closing a zlib stream
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest run that cannot overflow before the modulo
+    for block in data.chunks(5552) {
+        for &x in block {
+            a += x as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn filter(rgb: &[u8], stride: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(rgb.len() + rgb.len() / stride);
+    let zero = vec![0u8; stride];
+    let mut line = vec![0u8; stride];
+    let mut best = vec![0u8; stride];
+
+    for (y, row) in rgb.chunks(stride).enumerate() {
+        let up = if y == 0 { &zero[..] } else { &rgb[(y - 1) * stride..y * stride] };
+        let mut best_kind = 0;
+        let mut best_cost = u64::MAX;
+
+        for kind in 0..5u8 {
+            for i in 0..stride {
+                let a = if i >= 3 { row[i - 3] } else { 0 };
+                let b = up[i];
+                let c = if i >= 3 { up[i - 3] } else { 0 };
+                let pred = match kind {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                line[i] = row[i].wrapping_sub(pred);
+            }
+            let cost = line.iter().map(|&v| (v as i8).unsigned_abs() as u64).sum();
+            if cost < best_cost {
+                best_cost = cost;
+                best_kind = kind;
+                best.copy_from_slice(&line);
+            }
+        }
+
+        out.push(best_kind);
+        out.extend_from_slice(&best);
+    }
+    out
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // 32K window, default compression level
+    let mut out = vec![0x78, 0x9c];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    n: u32,
+}
+
+impl BitWriter {
+    // `bits` are stored least significant bit first
+    fn put(&mut self, bits: u32, count: u32) {
+        self.acc |= bits << self.n;
+        self.n += count;
+        while self.n >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.n -= 8;
+        }
+    }
+
+    // huffman codes go most significant bit first
+    fn put_code(&mut self, code: u32, len: u32) {
+        self.put(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+const LEN_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LEN_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+fn put_symbol(bw: &mut BitWriter, sym: u32) {
+    match sym {
+        0..=143 => bw.put_code(0x30 + sym, 8),
+        144..=255 => bw.put_code(0x190 + sym - 144, 9),
+        256..=279 => bw.put_code(sym - 256, 7),
+        _ => bw.put_code(0xc0 + sym - 280, 8),
+    }
+}
+
+fn put_match(bw: &mut BitWriter, len: usize, dist: usize) {
+    let l = LEN_BASE.iter().rposition(|&b| b as usize <= len).unwrap();
+    put_symbol(bw, 257 + l as u32);
+    bw.put((len - LEN_BASE[l] as usize) as u32, LEN_EXTRA[l] as u32);
+
+    let d = DIST_BASE.iter().rposition(|&b| b as usize <= dist).unwrap();
+    bw.put_code(d as u32, 5);
+    bw.put((dist - DIST_BASE[d] as usize) as u32, DIST_EXTRA[d] as u32);
+}
+
+const WINDOW: usize = 1 << 15;
+const HASH_BITS: u32 = 15;
+const MAX_CHAIN: usize = 64;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bw = BitWriter { out: Vec::new(), acc: 0, n: 0 };
+    // a single final block with the fixed codes
+    bw.put(1, 1);
+    bw.put(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let insert = |head: &mut Vec<usize>, prev: &mut Vec<usize>, i: usize| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(data, i);
+            prev[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+
+        if i + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - i);
+            let mut cand = head[hash(data, i)];
+            let mut chain = 0;
+            while cand != usize::MAX && i - cand <= WINDOW && chain < MAX_CHAIN {
+                let len = data[cand..].iter().zip(&data[i..i + max_len]).take_while(|(a, b)| a == b).count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - cand;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[cand % WINDOW];
+                // entries older than the window may have been overwritten
+                if next == usize::MAX || next >= cand {
+                    break;
+                }
+                cand = next;
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            put_match(&mut bw, best_len, best_dist);
+            for j in i..i + best_len {
+                insert(&mut head, &mut prev, j);
+            }
+            i += best_len;
+        } else {
+            put_symbol(&mut bw, data[i] as u32);
+            insert(&mut head, &mut prev, i);
+            i += 1;
+        }
+    }
+
+    put_symbol(&mut bw, 256);
+    bw.finish()
+}
@@ -0,0 +1,285 @@
+// Rendering of a scene seen from a camera into a linear framebuffer.
+//
+// The picture is cut into square tiles and worker threads take them one by
+// one from a shared counter, so a thread that got cheap tiles (only
+// background) simply takes more of them.
+//
+// Every pixel is computed on its own from its coordinates, which makes the
+// result bit-identical whatever the number of threads is.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::camera::Camera;
+use crate::hdr::{Framebuffer, RGBf};
+use crate::objects::Scene;
+use crate::III_vector::Ray;
+use crate::PPM::RGB;
+use crate::sampling::{Filter, Pattern, Rng};
+
+pub const TILE_SIZE: u32 = 32;
+
+// every pixel first gets `initial` rays ; only the ones whose rays saw
+// different objects or colors, or that differ from a neighbour, are shot
+// again with the full sample count. It is skipped unless that count is
+// above `initial`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Adaptive {
+    // a square number for the grid patterns
+    pub initial: u32,
+    // largest difference of a channel, 1.0 being white, still taken as flat
+    pub threshold: f32,
+}
+
+impl Adaptive {
+    // None for a negative or NaN threshold
+    pub fn new(threshold: f32) -> Option<Adaptive> {
+        if threshold.is_nan() || threshold < 0.0 {
+            return None;
+        }
+        Some(Adaptive {
+            initial: 4,
+            threshold,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub width: u32,
+    pub height: u32,
+    // rays per pixel, a square number for the grid patterns
+    pub samples: u32,
+    pub pattern: Pattern,
+    pub filter: Filter,
+    // None shoots `samples` rays through every pixel
+    pub adaptive: Option<Adaptive>,
+    pub threads: usize,
+    pub tile_size: u32,
+    // what rays that hit nothing see
+    pub background: RGBf,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            width: 800,
+            height: 800,
+            samples: 1,
+            pattern: Pattern::Grid,
+            filter: Filter::Box,
+            adaptive: None,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            tile_size: TILE_SIZE,
+            background: RGBf::from_rgb(RGB::new(10, 10, 10)),
+        }
+    }
+}
+
+struct Sampler<'a> {
+    scene: &'a Scene,
+    camera: &'a Camera,
+    settings: &'a Settings,
+}
+
+impl<'a> Sampler<'a> {
+    // the samples of a pixel are spread over the support of the filter around
+    // its center and averaged with the filter weights, in linear space ;
+    // `each` sees every ray with the color it brought back, None for the
+    // samples the camera does not see through
+    fn pixel<F>(&self, x: u32, y: u32, n: u32, mut each: F) -> RGBf
+        where F: FnMut(Option<&Ray<f32>>, RGBf) {
+        let (w, h) = (self.settings.width as f32, self.settings.height as f32);
+        let filter = self.settings.filter;
+        let spread = 2.0 * filter.radius();
+
+        let mut rng = Rng::for_pixel(x, y);
+        let mut lens_rng = rng.split();
+        let mut sum = RGBf::default();
+        let mut plain = RGBf::default();
+        let mut weights = 0.0;
+
+        for (sx, sy) in self.settings.pattern.samples(n, rng) {
+            let (dx, dy) = ((sx - 0.5) * spread, (sy - 0.5) * spread);
+            let (u, v) = ((x as f32 + 0.5 + dx) / w, (y as f32 + 0.5 + dy) / h);
+            let ray = if self.camera.aperture() > 0.0 {
+                self.camera.ray_through_lens(u, v, lens_rng.next_f32(), lens_rng.next_f32())
+            } else {
+                self.camera.ray(u, v)
+            };
+            // what the camera does not see through keeps the background
+            let c = ray.as_ref()
+                .and_then(|r| self.scene.radiance_on_dir(r.root, r.dir))
+                .unwrap_or(self.settings.background);
+            each(ray.as_ref(), c);
+            let k = filter.weight(dx, dy);
+            sum += c.scale(k);
+            plain += c;
+            weights += k;
+        }
+
+        // negative lobes (Mitchell) may cancel out with very few samples
+        if weights > 1e-6 {
+            sum.scale(1.0 / weights)
+        } else {
+            plain.scale(1.0 / n.max(1) as f32)
+        }
+    }
+}
+
+// what the first pass of adaptive sampling learnt about a pixel
+#[derive(Clone, Copy, Default)]
+struct Probe {
+    color: RGBf,
+    // the object seen by the first ray, None for the background
+    hit: Option<usize>,
+    // the rays did not agree with each other
+    busy: bool,
+}
+
+// channels are compared as displayed, a highlight at 5.0 and one at 8.0 are
+// both white
+fn differ(a: RGBf, b: RGBf, threshold: f32) -> bool {
+    let d = |p: f32, q: f32| (p.clamp(0.0, 1.0) - q.clamp(0.0, 1.0)).abs();
+    d(a.r, b.r).max(d(a.g, b.g)).max(d(a.b, b.b)) > threshold
+}
+
+pub fn render(scene: &Scene, camera: &Camera, settings: &Settings) -> Framebuffer {
+    let sampler = Sampler { scene, camera, settings };
+    let (width, height) = (settings.width, settings.height);
+
+    let adaptive = match settings.adaptive {
+        Some(a) if a.initial >= 1 && a.initial < settings.samples => a,
+        _ => {
+            return render_tiles(width, height, settings.tile_size, settings.threads, |x, y| {
+                sampler.pixel(x, y, settings.samples, |_, _| {})
+            });
+        }
+    };
+
+    let probes = run_tiles(width, height, settings.tile_size, settings.threads, |x, y| {
+        let mut first: Option<(Option<usize>, RGBf)> = None;
+        let mut busy = false;
+        let color = sampler.pixel(x, y, adaptive.initial, |ray, c| {
+            let hit = ray.and_then(|r| scene.ind_viewing(r.root, r.dir));
+            match first {
+                None => first = Some((hit, c)),
+                Some((h, f)) => busy |= h != hit || differ(f, c, adaptive.threshold),
+            }
+        });
+        Probe { color, hit: first.and_then(|(h, _)| h), busy }
+    });
+
+    let probe = |x: u32, y: u32| probes[y as usize * width as usize + x as usize];
+    render_tiles(width, height, settings.tile_size, settings.threads, |x, y| {
+        let p = probe(x, y);
+        let neighbours = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ];
+        let edge = neighbours.iter()
+            .filter(|&&(nx, ny)| nx < width && ny < height)
+            .any(|&(nx, ny)| {
+                let q = probe(nx, ny);
+                q.hit != p.hit || differ(q.color, p.color, adaptive.threshold)
+            });
+        // the full set is shot anew, the first rays only decided where
+        if p.busy || edge {
+            sampler.pixel(x, y, settings.samples, |_, _| {})
+        } else {
+            p.color
+        }
+    })
+}
+
+// the closures given to render_tiles() borrow the scene from every worker
+const _: fn() = || {
+    fn assert_sync<T: Sync>() {}
+    assert_sync::<Scene>();
+};
+
+#[derive(Clone, Copy)]
+struct Tile {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+fn tiles(width: u32, height: u32, size: u32) -> Vec<Tile> {
+    let mut out = Vec::new();
+    for y0 in (0..height).step_by(size as usize) {
+        for x0 in (0..width).step_by(size as usize) {
+            out.push(Tile {
+                x0,
+                y0,
+                x1: (x0 + size).min(width),
+                y1: (y0 + size).min(height),
+            });
+        }
+    }
+    out
+}
+
+// calls `shade(x, y)` for every pixel of a `width` x `height` picture using
+// `threads` workers ; `threads` = 1 renders on the calling thread
+pub fn render_tiles<F>(width: u32, height: u32, tile_size: u32, threads: usize, shade: F) -> Framebuffer
+    where F: Fn(u32, u32) -> RGBf + Sync {
+    let mut fb = Framebuffer::new(width, height);
+    let colors = run_tiles(width, height, tile_size, threads, shade);
+    for (i, c) in colors.into_iter().enumerate() {
+        fb.set_pixel(i as u32 % width, i as u32 / width, c);
+    }
+    fb
+}
+
+// the same for anything computed per pixel, returned row by row
+fn run_tiles<T, F>(width: u32, height: u32, tile_size: u32, threads: usize, shade: F) -> Vec<T>
+    where T: Copy + Default + Send, F: Fn(u32, u32) -> T + Sync {
+    let tiles = tiles(width, height, tile_size.max(1));
+    let mut out = vec![T::default(); width as usize * height as usize];
+
+    let render_tile = |t: Tile| -> Vec<T> {
+        let mut out = Vec::with_capacity(((t.x1 - t.x0) * (t.y1 - t.y0)) as usize);
+        for y in t.y0..t.y1 {
+            for x in t.x0..t.x1 {
+                out.push(shade(x, y));
+            }
+        }
+        out
+    };
+
+    let done: Vec<(usize, Vec<T>)> = if threads <= 1 {
+        tiles.iter().map(|t| render_tile(*t)).enumerate().collect()
+    } else {
+        let next = AtomicUsize::new(0);
+        thread::scope(|s| {
+            let workers: Vec<_> = (0..threads.min(tiles.len()))
+                .map(|_| s.spawn(|| {
+                    let mut mine = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        match tiles.get(i) {
+                            Some(t) => mine.push((i, render_tile(*t))),
+                            None => return mine,
+                        }
+                    }
+                }))
+                .collect();
+            workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+        })
+    };
+
+    for (i, values) in done {
+        let t = tiles[i];
+        let mut values = values.into_iter();
+        for y in t.y0..t.y1 {
+            for x in t.x0..t.x1 {
+                out[y as usize * width as usize + x as usize] = values.next().unwrap();
+            }
+        }
+    }
+    out
+}
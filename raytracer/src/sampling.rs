@@ -0,0 +1,233 @@
+// Random numbers seeded from pixel coordinates, sample warping, sample
+// patterns and reconstruction filters, shared by the camera and the renderer.
+
+use std::f32::consts::PI;
+
+// SplitMix64, small and good enough to scatter samples
+#[derive(Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    // a generator of its own for every pixel
+    pub fn for_pixel(x: u32, y: u32) -> Rng {
+        let mut r = Rng::new(((y as u64) << 32) | x as u64);
+        r.next_u64();
+        r
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // an independent generator, e.g. for the lens while this one jitters
+    pub fn split(&mut self) -> Rng {
+        Rng::new(self.next_u64())
+    }
+}
+
+// maps the unit square onto the unit disk keeping strata apart (Shirley and
+// Chiu's concentric mapping)
+pub fn concentric_disk(u: f32, v: f32) -> (f32, f32) {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, phi) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (r * phi.cos(), r * phi.sin())
+}
+
+// where the samples of a pixel go, as points of the unit square
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Pattern {
+    // cell centers of a regular grid, the sample count must be a square
+    Grid,
+    // one random point in every cell of the grid
+    Jittered,
+    // Halton sequence in bases 2 and 3, randomly shifted for every pixel
+    Halton,
+    // first two Sobol dimensions with random digit scrambling per pixel
+    Sobol,
+}
+
+impl Pattern {
+    pub fn from_name(name: &str) -> Option<Pattern> {
+        match name.to_ascii_lowercase().as_str() {
+            "grid" => Some(Pattern::Grid),
+            "jittered" | "stratified" => Some(Pattern::Jittered),
+            "halton" => Some(Pattern::Halton),
+            "sobol" => Some(Pattern::Sobol),
+            _ => None,
+        }
+    }
+
+    pub fn needs_square_count(self) -> bool {
+        matches!(self, Pattern::Grid | Pattern::Jittered)
+    }
+
+    pub fn samples(self, n: u32, mut rng: Rng) -> PixelSamples {
+        let side = ((n as f32).sqrt().round() as u32).max(1);
+        let shift = (rng.next_f32(), rng.next_f32());
+        let scramble = ((rng.next_u64() >> 32) as u32, (rng.next_u64() >> 32) as u32);
+        PixelSamples {
+            pattern: self,
+            n,
+            i: 0,
+            side,
+            rng,
+            shift,
+            scramble,
+        }
+    }
+}
+
+pub struct PixelSamples {
+    pattern: Pattern,
+    n: u32,
+    i: u32,
+    side: u32,
+    rng: Rng,
+    shift: (f32, f32),
+    scramble: (u32, u32),
+}
+
+impl Iterator for PixelSamples {
+    type Item = (f32, f32);
+
+    fn next(&mut self) -> Option<(f32, f32)> {
+        if self.i >= self.n {
+            return None;
+        }
+        let i = self.i;
+        self.i += 1;
+
+        let side = self.side as f32;
+        let (cx, cy) = ((i % self.side) as f32, (i / self.side) as f32);
+        Some(match self.pattern {
+            Pattern::Grid => ((cx + 0.5) / side, (cy + 0.5) / side),
+            Pattern::Jittered => ((cx + self.rng.next_f32()) / side, (cy + self.rng.next_f32()) / side),
+            Pattern::Halton => (
+                (radical_inverse(i, 2) + self.shift.0).fract(),
+                (radical_inverse(i, 3) + self.shift.1).fract(),
+            ),
+            Pattern::Sobol => (
+                to_unit(i.reverse_bits() ^ self.scramble.0),
+                to_unit(sobol2(i) ^ self.scramble.1),
+            ),
+        })
+    }
+}
+
+fn radical_inverse(mut i: u32, base: u32) -> f32 {
+    let inv = 1.0 / base as f32;
+    let mut f = inv;
+    let mut r = 0.0;
+    while i > 0 {
+        r += (i % base) as f32 * f;
+        i /= base;
+        f *= inv;
+    }
+    r
+}
+
+// second dimension of the Sobol sequence, the first one is the bit reversal
+fn sobol2(mut i: u32) -> u32 {
+    let mut v = 1u32 << 31;
+    let mut r = 0;
+    while i != 0 {
+        if i & 1 != 0 {
+            r ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+    r
+}
+
+fn to_unit(bits: u32) -> f32 {
+    // 24 bits is all a f32 below 1.0 can hold
+    (bits >> 8) as f32 / (1u32 << 24) as f32
+}
+
+// how much a sample counts in the pixel, from its offset to the pixel center
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    Box,
+    Tent,
+    Gaussian,
+    // Mitchell-Netravali with B = C = 1/3
+    Mitchell,
+}
+
+impl Filter {
+    pub fn from_name(name: &str) -> Option<Filter> {
+        match name.to_ascii_lowercase().as_str() {
+            "box" => Some(Filter::Box),
+            "tent" | "triangle" => Some(Filter::Tent),
+            "gaussian" => Some(Filter::Gaussian),
+            "mitchell" => Some(Filter::Mitchell),
+            _ => None,
+        }
+    }
+
+    // half width of the support, in pixels ; samples are spread over it
+    pub fn radius(self) -> f32 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+        }
+    }
+
+    fn weight_1d(self, d: f32) -> f32 {
+        let d = d.abs();
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => (1.0 - d).max(0.0),
+            Filter::Gaussian => {
+                let alpha = 2.0;
+                let r = self.radius();
+                ((-alpha * d * d).exp() - (-alpha * r * r).exp()).max(0.0)
+            }
+            Filter::Mitchell => {
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                if d < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * d * d * d
+                        + (-18.0 + 12.0 * b + 6.0 * c) * d * d
+                        + (6.0 - 2.0 * b)) / 6.0
+                } else if d < 2.0 {
+                    ((-b - 6.0 * c) * d * d * d
+                        + (6.0 * b + 30.0 * c) * d * d
+                        + (-12.0 * b - 48.0 * c) * d
+                        + (8.0 * b + 24.0 * c)) / 6.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    // (dx, dy) in pixels from the pixel center
+    pub fn weight(self, dx: f32, dy: f32) -> f32 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+}
@@ -0,0 +1,942 @@
+// Loader for the text scene description.
+//
+// The format is a small subset of TOML: `[camera]` holds the view settings,
+// every `[[sphere]]`, `[[plane]]`, `[[quad]]`, `[[triangle]]`, `[[mesh]]`,
+// `[[box]]`, `[[cylinder]]`, `[[cone]]`, `[[disk]]`, `[[torus]]` and
+// `[[light]]` table adds one object to the scene. Every shape may also be
+// placed with `scale`, `rotate` and `translate`, see the end of the list.
+// Values are numbers, quoted strings, booleans or arrays of those, and `#`
+// starts a comment. Every error carries the line it was found on.
+//
+//     [camera]
+//     position = [-1.0, 0.0, 0.0]
+//     look_at = [0.0, 0.0, 0.0]
+//     up = [0.0, 0.0, 1.0]       # optional, +z by default
+//     fov = 112.6                # vertical, in degrees
+//     projection = "perspective" # or "orthographic" (with `height`),
+//                                # "equirectangular" or "fisheye"
+//     aperture = 0.05            # optional lens radius, 0 keeps all sharp
+//     focus_distance = 2.0       # optional, the distance to `look_at` by default
+//
+//     [[sphere]]
+//     center = [1.0, 0.3, 0.5]
+//     radius = 0.7
+//     surface = "solid"          # "solid", "mirror" or "transparent"
+//     color = [77, 248, 255]     # only for "solid"
+//     refraction = 1.3           # only for "transparent"
+//     specular = 1.3
+//     diffuse = 1.5              # optional, 1.0 by default
+//     ambient = 1.0              # optional, 1.0 by default
+//     shininess = 100.0
+//
+//     [[plane]]                  # infinite, same surface keys as a sphere
+//     point = [0.0, 0.0, -1.0]
+//     normal = [0.0, 0.0, 1.0]
+//     ...
+//
+//     [[quad]]                   # the parallelogram corner + a*u + b*v,
+//     corner = [2.0, -1.0, -1.0] # a and b in [0, 1], with surface keys too
+//     u = [0.0, 2.0, 0.0]
+//     v = [0.0, 0.0, 2.0]
+//     ...
+//
+//     [[triangle]]
+//     a = [1.0, 0.0, 0.0]
+//     b = [1.0, 1.0, 0.0]
+//     c = [1.0, 0.0, 1.0]
+//     ...
+//
+//     [[mesh]]
+//     vertices = [[1, 0, 0], [1, 1, 0],  # arrays may span lines
+//                 [1, 0, 1], [1, 1, 1]]
+//     faces = [[0, 1, 2], [2, 1, 3]]   # indices into `vertices`
+//     normals = [[-1, 0, 0], ...]      # optional, one per vertex ; averaged
+//     ...                              # from the faces otherwise
+//
+//     [[mesh]]
+//     file = "models/teapot.obj"       # Wavefront OBJ, next to the scene file
+//     surface = "mirror"               # optional, replaces the MTL materials
+//     ...
+//
+//     [[box]]
+//     min = [0.0, 0.0, 0.0]            # along the world axes,
+//     max = [1.0, 1.0, 1.0]
+//     center = [0.5, 0.5, 0.5]         # or turned : `size` is measured
+//     size = [1.0, 1.0, 1.0]           # along `x_axis`, `y_axis` and
+//     x_axis = [1.0, 1.0, 0.0]         # their cross product
+//     y_axis = [-1.0, 1.0, 0.0]
+//     ...
+//
+//     [[cylinder]]                     # closed at both ends
+//     base = [1.0, 0.0, -1.0]
+//     top = [1.0, 0.0, 0.0]
+//     radius = 0.3
+//     ...
+//
+//     [[cone]]                         # closed at the base
+//     base = [1.0, 0.0, -1.0]
+//     apex = [1.0, 0.0, 0.0]
+//     radius = 0.3
+//     ...
+//
+//     [[disk]]
+//     center = [1.0, 0.0, 0.0]
+//     normal = [0.0, 0.0, 1.0]
+//     radius = 0.5
+//     ...
+//
+//     [[torus]]
+//     center = [1.0, 0.0, 0.0]
+//     axis = [0.0, 0.0, 1.0]           # optional, +z by default
+//     major = 0.5                      # radius of the ring
+//     minor = 0.1                      # radius of the tube, below `major`
+//     ...
+//
+//     [[sphere]]                       # any shape, here an ellipsoid :
+//     center = [0.0, 0.0, 0.0]
+//     radius = 1.0
+//     scale = [0.5, 0.2, 0.2]          # applied about the world origin,
+//     rotate = [0.0, 0.0, 1.0, 30.0]   # in this order ; an axis and an
+//     translate = [2.0, 0.0, -0.5]     # angle in degrees for `rotate`
+//     ...                              # (a model used twice is shared)
+//
+//     [[light]]                        # in the units of the picture, where
+//     position = [-0.6, 0.8, 1.3]      # 1.0 shows a white surface as white
+//     intensity = 4.5                  # optional, 1.0 by default : a white
+//                                      # surface facing the light d units
+//                                      # away gets intensity / d^2
+//     specular = 0.7                   # optional weights of the highlights
+//     diffuse = 1.0                    # and of the diffuse term, 1.0 both
+//     ambient = 0.02                   # optional, added everywhere whatever
+//                                      # the distance, 0.0 by default
+//     shape = "point"                  # optional ; area lights give soft
+//                                      # shadows : "sphere" with `radius`,
+//                                      # "rect" with edges `u` and `v`
+//                                      # around `position`, "disk" with
+//                                      # `normal` and `radius`
+//     samples = 16                     # optional, shadow rays of an area light
+//     color = [255, 214, 170]          # optional, white by default
+//     falloff = "inverse_square"       # optional and the default, "none"
+//                                      # lights as from one unit away ; or
+//     attenuation = [1.0, 0.0, 0.5]    # 1 / (a + b d + c d^2) instead
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::III_vector::{Transform, Vec3};
+use crate::camera::{Camera, Projection};
+use crate::obj;
+use crate::shapes::{Cone, Cuboid, Cylinder, Disk, Instance, Torus};
+use crate::PPM::RGB;
+use crate::hdr::RGBf;
+use crate::objects::{Scene, Shape, Sphere, Plane, Quad, Triangle, TriangleMesh, Light, SurfaceOptions,
+    LightOptions, SurfaceType, Falloff};
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse { line: usize, msg: String },
+}
+
+impl SceneError {
+    fn at(line: usize, msg: String) -> SceneError {
+        SceneError::Parse { line, msg }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "cannot read scene: {}", e),
+            SceneError::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(e: io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CameraOptions {
+    pub position: Vec3<f32>,
+    pub look_at: Vec3<f32>,
+    pub up: Vec3<f32>,
+    // vertical, in degrees
+    pub fov: f32,
+    pub projection: Projection,
+    pub aperture: f32,
+    // None focuses on `look_at`
+    pub focus_distance: Option<f32>,
+}
+
+impl Default for CameraOptions {
+    // the view the tracer always had : from (-1, 0, 0) along +x, with the
+    // [-1.5, 1.5] square seen at distance 1
+    fn default() -> Self {
+        CameraOptions {
+            position: Vec3::new(-1.0, 0.0, 0.0),
+            look_at: Vec3::new(0.0, 0.0, 0.0),
+            up: Vec3::new(0.0, 0.0, 1.0),
+            fov: 2.0 * 1.5f32.atan().to_degrees(),
+            projection: Projection::Perspective,
+            aperture: 0.0,
+            focus_distance: None,
+        }
+    }
+}
+
+impl CameraOptions {
+    // None if the options do not describe a valid view
+    pub fn camera(&self, width: u32, height: u32) -> Option<Camera> {
+        let cam = Camera::new(self.position, self.look_at, self.up, self.fov,
+            width as f32 / height as f32, self.projection)?;
+        let focus = self.focus_distance.unwrap_or_else(|| cam.focus_distance());
+        cam.with_lens(self.aperture, focus)
+    }
+}
+
+pub struct SceneFile {
+    pub scene: Scene,
+    pub camera: CameraOptions,
+}
+
+impl SceneFile {
+    // models are looked for next to the scene file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneFile, SceneError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        SceneFile::parse_in(&text, path.parent().unwrap_or_else(|| Path::new("")))
+    }
+
+    // models are looked for from the current directory
+    pub fn parse(text: &str) -> Result<SceneFile, SceneError> {
+        SceneFile::parse_in(text, Path::new(""))
+    }
+
+    fn parse_in(text: &str, dir: &Path) -> Result<SceneFile, SceneError> {
+        let mut scene = Scene::new();
+        let mut camera = CameraOptions::default();
+        let mut camera_seen = false;
+        // OBJ files already read, by path
+        let mut models = HashMap::new();
+
+        for mut table in parse_tables(text)? {
+            match (table.name.as_str(), table.array) {
+                ("camera", false) => {
+                    if camera_seen {
+                        return Err(SceneError::at(table.line, "duplicate [camera] table".to_string()));
+                    }
+                    camera_seen = true;
+                    camera = camera_from(&mut table)?;
+                }
+                ("sphere", true) => place(&mut scene, &mut table, sphere_from)?,
+                ("plane", true) => place(&mut scene, &mut table, plane_from)?,
+                ("quad", true) => place(&mut scene, &mut table, quad_from)?,
+                ("triangle", true) => place(&mut scene, &mut table, triangle_from)?,
+                ("mesh", true) => mesh_from(&mut scene, &mut table, dir, &mut models)?,
+                ("box", true) => place(&mut scene, &mut table, box_from)?,
+                ("cylinder", true) => place(&mut scene, &mut table, cylinder_from)?,
+                ("cone", true) => place(&mut scene, &mut table, cone_from)?,
+                ("disk", true) => place(&mut scene, &mut table, disk_from)?,
+                ("torus", true) => place(&mut scene, &mut table, torus_from)?,
+                ("light", true) => scene.add_light(light_from(&mut table)?),
+                (name, true) => {
+                    return Err(SceneError::at(table.line, format!("unknown table [[{}]]", name)));
+                }
+                (name, false) => {
+                    return Err(SceneError::at(table.line, format!("unknown table [{}]", name)));
+                }
+            }
+            table.finish()?;
+        }
+
+        Ok(SceneFile { scene, camera })
+    }
+}
+
+fn camera_from(t: &mut Table) -> Result<CameraOptions, SceneError> {
+    let mut cam = CameraOptions::default();
+    if let Some(p) = t.vec3("position")? {
+        cam.position = p;
+    }
+    if let Some(p) = t.vec3("look_at")? {
+        cam.look_at = p;
+    }
+    if let Some(u) = t.vec3("up")? {
+        cam.up = u;
+    }
+    let fov = t.num("fov")?;
+    if let Some((f, _)) = fov {
+        cam.fov = f;
+    }
+
+    if let Some((name, line)) = t.str("projection")? {
+        cam.projection = match name.as_str() {
+            "perspective" => Projection::Perspective,
+            "orthographic" => Projection::Orthographic { height: t.require_positive("height")? },
+            "equirectangular" => Projection::Equirectangular,
+            "fisheye" => Projection::Fisheye,
+            other => return Err(SceneError::at(line, format!(
+                "unknown projection \"{}\", expected \"perspective\", \"orthographic\", \
+                 \"equirectangular\" or \"fisheye\"", other))),
+        };
+    }
+    if let Some((f, line)) = fov {
+        // the other projections do not use it
+        let (ok, max) = match cam.projection {
+            Projection::Perspective => (f > 0.0 && f < 180.0, 180),
+            Projection::Fisheye => (f > 0.0 && f <= 360.0, 360),
+            _ => (true, 0),
+        };
+        if !ok {
+            return Err(SceneError::at(line, format!("`fov` must be between 0 and {} degrees, got {}", max, f)));
+        }
+    }
+
+    if let Some(a) = t.non_negative("aperture")? {
+        cam.aperture = a;
+    }
+    cam.focus_distance = t.positive("focus_distance")?;
+
+    if cam.camera(1, 1).is_none() {
+        return Err(SceneError::at(t.line,
+            "`look_at` must differ from `position` and `up` must not point along the view".to_string()));
+    }
+    Ok(cam)
+}
+
+fn surface_from(t: &mut Table) -> Result<SurfaceOptions, SceneError> {
+    let (kind, line) = t.require_str("surface")?;
+    let surface_type = match kind.as_str() {
+        "solid" => {
+            let color = t.color("color")?
+                .ok_or_else(|| SceneError::at(t.line, "solid surface needs a `color`".to_string()))?;
+            SurfaceType::Solid(color)
+        }
+        "mirror" => SurfaceType::Mirror,
+        "transparent" => SurfaceType::Transparent(t.require_positive("refraction")?),
+        other => {
+            return Err(SceneError::at(line, format!(
+                "unknown surface \"{}\", expected \"solid\", \"mirror\" or \"transparent\"", other)));
+        }
+    };
+
+    let spec = t.require_non_negative("specular")?;
+    let diff = t.non_negative("diffuse")?.unwrap_or(1.0);
+    let amb = t.non_negative("ambient")?.unwrap_or(1.0);
+    let shin = t.require_non_negative("shininess")?;
+    Ok(SurfaceOptions::new(spec, diff, amb, shin, surface_type))
+}
+
+fn sphere_from(t: &mut Table) -> Result<Sphere, SceneError> {
+    let center = t.require_vec3("center")?;
+    let (radius, line) = t.require_num("radius")?;
+    let opt = surface_from(t)?;
+    Sphere::new(center, radius, opt)
+        .filter(|_| radius > 0.0)
+        .ok_or_else(|| SceneError::at(line, format!("sphere radius must be positive, got {}", radius)))
+}
+
+fn plane_from(t: &mut Table) -> Result<Plane, SceneError> {
+    let point = t.require_vec3("point")?;
+    let normal = t.require_vec3("normal")?;
+    let opt = surface_from(t)?;
+    Plane::new(point, normal, opt)
+        .ok_or_else(|| SceneError::at(t.line, "plane `normal` must not be zero".to_string()))
+}
+
+fn quad_from(t: &mut Table) -> Result<Quad, SceneError> {
+    let corner = t.require_vec3("corner")?;
+    let u = t.require_vec3("u")?;
+    let v = t.require_vec3("v")?;
+    let opt = surface_from(t)?;
+    Quad::new(corner, u, v, opt)
+        .ok_or_else(|| SceneError::at(t.line, "quad edges `u` and `v` must not be zero or parallel".to_string()))
+}
+
+fn triangle_from(t: &mut Table) -> Result<Triangle, SceneError> {
+    let a = t.require_vec3("a")?;
+    let b = t.require_vec3("b")?;
+    let c = t.require_vec3("c")?;
+    let opt = surface_from(t)?;
+    Triangle::new(a, b, c, opt)
+        .ok_or_else(|| SceneError::at(t.line, "triangle corners must not be on a line".to_string()))
+}
+
+// the parts of an OBJ file, shared between the tables using it
+type Model = Vec<Arc<TriangleMesh>>;
+
+fn mesh_from(scene: &mut Scene, t: &mut Table, dir: &Path, models: &mut HashMap<PathBuf, Model>)
+    -> Result<(), SceneError> {
+    if let Some((file, line)) = t.str("file")? {
+        if t.has("vertices") || t.has("faces") || t.has("normals") {
+            return Err(SceneError::at(line, "a mesh comes either from a `file` or from `vertices`".to_string()));
+        }
+        let path = dir.join(&file);
+        if !models.contains_key(&path) {
+            let parts = obj::load_obj(&path).map_err(|e| SceneError::at(line, e.to_string()))?;
+            models.insert(path.clone(), parts.into_iter().map(|p| Arc::new(p.mesh)).collect());
+        }
+        // the surface keys, if any, replace the materials of the file
+        let opt = if t.has("surface") { Some(surface_from(t)?) } else { None };
+        let transform = transform_from(t)?;
+        for part in &models[&path] {
+            match (opt, transform) {
+                (None, Some(tr)) => scene.add_obj(Instance::shared(part.clone(), tr)),
+                (Some(o), tr) => add_placed(scene, part.as_ref().clone().with_opt(o), tr),
+                (None, None) => scene.add_obj(part.as_ref().clone()),
+            }
+        }
+        return Ok(());
+    }
+    place(scene, t, mesh_data_from)
+}
+
+fn mesh_data_from(t: &mut Table) -> Result<TriangleMesh, SceneError> {
+    let to_vec3 = |rows: Vec<[f32; 3]>| rows.into_iter().map(|[x, y, z]| Vec3::new(x, y, z)).collect::<Vec<_>>();
+    let (vertices, _) = t.rows::<3>("vertices")?.ok_or_else(|| t.missing("vertices"))?;
+    let (faces, line) = t.rows::<3>("faces")?.ok_or_else(|| t.missing("faces"))?;
+    let normals = t.rows::<3>("normals")?;
+
+    let mut indices = Vec::with_capacity(faces.len());
+    for f in faces {
+        if f.iter().any(|&i| i < 0.0 || i.fract() != 0.0 || i as usize >= vertices.len()) {
+            return Err(SceneError::at(line, format!(
+                "face {:?} does not index one of the {} vertices", f, vertices.len())));
+        }
+        indices.push([f[0] as usize, f[1] as usize, f[2] as usize]);
+    }
+    let opt = surface_from(t)?;
+    let normals_line = normals.as_ref().map_or(t.line, |(_, l)| *l);
+    TriangleMesh::new(to_vec3(vertices), indices, normals.map(|(n, _)| to_vec3(n)), opt)
+        .ok_or_else(|| SceneError::at(normals_line,
+            "a mesh needs faces and as many non-zero `normals` as vertices".to_string()))
+}
+
+// reads a shape with `read`, then the transform keys of its table
+fn place<S, F>(scene: &mut Scene, t: &mut Table, read: F) -> Result<(), SceneError>
+    where S: Shape + 'static, F: FnOnce(&mut Table) -> Result<S, SceneError> {
+    let shape = read(t)?;
+    let transform = transform_from(t)?;
+    add_placed(scene, shape, transform);
+    Ok(())
+}
+
+fn add_placed<S: Shape + 'static>(scene: &mut Scene, shape: S, transform: Option<Transform>) {
+    match transform {
+        Some(tr) => scene.add_obj(Instance::new(shape, tr)),
+        None => scene.add_obj(shape),
+    }
+}
+
+// `scale`, then `rotate`, then `translate` ; None if the table has none
+fn transform_from(t: &mut Table) -> Result<Option<Transform>, SceneError> {
+    let mut out: Option<Transform> = None;
+    let mut push = |next: Transform| out = Some(out.map_or(next, |o| o.then(next)));
+
+    if let Some(([x, y, z], line)) = t.numbers::<3>("scale")? {
+        let s = Transform::scale(Vec3::new(x, y, z))
+            .ok_or_else(|| SceneError::at(line, "`scale` factors must not be zero".to_string()))?;
+        push(s);
+    }
+    if let Some(([x, y, z, degrees], line)) = t.numbers::<4>("rotate")? {
+        let r = Transform::rotate(Vec3::new(x, y, z), degrees)
+            .ok_or_else(|| SceneError::at(line, "`rotate` needs a non-zero axis, then an angle".to_string()))?;
+        push(r);
+    }
+    if let Some(v) = t.vec3("translate")? {
+        push(Transform::translate(v));
+    }
+    Ok(out)
+}
+
+fn box_from(t: &mut Table) -> Result<Cuboid, SceneError> {
+    let b = if t.has("min") || t.has("max") {
+        let min = t.require_vec3("min")?;
+        let max = t.require_vec3("max")?;
+        let opt = surface_from(t)?;
+        Cuboid::new(min, max, opt)
+    } else {
+        let center = t.require_vec3("center")?;
+        let size = t.require_vec3("size")?;
+        let x_axis = t.vec3("x_axis")?.unwrap_or_else(|| Vec3::new(1.0, 0.0, 0.0));
+        let y_axis = t.vec3("y_axis")?.unwrap_or_else(|| Vec3::new(0.0, 1.0, 0.0));
+        let opt = surface_from(t)?;
+        Cuboid::oriented(center, size, x_axis, y_axis, opt)
+    };
+    b.ok_or_else(|| SceneError::at(t.line,
+        "a box needs `max` above `min` (or a positive `size`) and axes that are not parallel".to_string()))
+}
+
+fn cylinder_from(t: &mut Table) -> Result<Cylinder, SceneError> {
+    let base = t.require_vec3("base")?;
+    let top = t.require_vec3("top")?;
+    let radius = t.require_positive("radius")?;
+    let opt = surface_from(t)?;
+    Cylinder::new(base, top, radius, opt)
+        .ok_or_else(|| SceneError::at(t.line, "cylinder `top` must differ from `base`".to_string()))
+}
+
+fn cone_from(t: &mut Table) -> Result<Cone, SceneError> {
+    let base = t.require_vec3("base")?;
+    let apex = t.require_vec3("apex")?;
+    let radius = t.require_positive("radius")?;
+    let opt = surface_from(t)?;
+    Cone::new(base, apex, radius, opt)
+        .ok_or_else(|| SceneError::at(t.line, "cone `apex` must differ from `base`".to_string()))
+}
+
+fn disk_from(t: &mut Table) -> Result<Disk, SceneError> {
+    let center = t.require_vec3("center")?;
+    let normal = t.require_vec3("normal")?;
+    let radius = t.require_positive("radius")?;
+    let opt = surface_from(t)?;
+    Disk::new(center, normal, radius, opt)
+        .ok_or_else(|| SceneError::at(t.line, "disk `normal` must not be zero".to_string()))
+}
+
+fn torus_from(t: &mut Table) -> Result<Torus, SceneError> {
+    let center = t.require_vec3("center")?;
+    let axis = t.vec3("axis")?.unwrap_or_else(|| Vec3::new(0.0, 0.0, 1.0));
+    let major = t.require_positive("major")?;
+    let minor = t.require_positive("minor")?;
+    let opt = surface_from(t)?;
+    Torus::new(center, axis, major, minor, opt)
+        .ok_or_else(|| SceneError::at(t.line, "torus needs a non-zero `axis` and `minor` below `major`".to_string()))
+}
+
+fn light_from(t: &mut Table) -> Result<Light, SceneError> {
+    let center = t.require_vec3("position")?;
+    let spec = t.non_negative("specular")?.unwrap_or(1.0);
+    let diff = t.non_negative("diffuse")?.unwrap_or(1.0);
+    let amb = t.non_negative("ambient")?.unwrap_or(0.0);
+    let mut opt = LightOptions::new(spec, diff, amb);
+    if let Some(c) = t.color("color")? {
+        opt = opt.with_color(RGBf::from_rgb(c));
+    }
+    if let Some(k) = t.non_negative("intensity")? {
+        opt = opt.with_intensity(k);
+    }
+    opt = opt.with_falloff(falloff_from(t)?);
+
+    let (shape, line) = t.str("shape")?.unwrap_or_else(|| ("point".to_string(), t.line));
+    let bad = |msg: &str| SceneError::at(line, msg.to_string());
+    let mut light = match shape.as_str() {
+        "point" => Light::new(center, opt),
+        "sphere" => Light::sphere(center, t.require_positive("radius")?, opt)
+            .ok_or_else(|| bad("light `radius` must be positive"))?,
+        "rect" => {
+            let u = t.require_vec3("u")?;
+            let v = t.require_vec3("v")?;
+            Light::rect(center, u, v, opt).ok_or_else(|| bad("light edges `u` and `v` must not be zero or parallel"))?
+        }
+        "disk" => {
+            let normal = t.require_vec3("normal")?;
+            let radius = t.require_positive("radius")?;
+            Light::disk(center, normal, radius, opt).ok_or_else(|| bad("light `normal` must not be zero"))?
+        }
+        other => {
+            return Err(SceneError::at(line, format!(
+                "unknown light shape \"{}\", expected \"point\", \"sphere\", \"rect\" or \"disk\"", other)));
+        }
+    };
+    if let Some((n, line)) = t.num("samples")? {
+        if shape == "point" {
+            return Err(SceneError::at(line, "`samples` is only for area lights, a point light casts one ray".to_string()));
+        }
+        if n < 1.0 || n.fract() != 0.0 {
+            return Err(SceneError::at(line, format!("`samples` must be a whole number above 0, got {}", n)));
+        }
+        light = light.with_samples(n as u32);
+    }
+    Ok(light)
+}
+
+fn falloff_from(t: &mut Table) -> Result<Falloff, SceneError> {
+    let falloff = t.str("falloff")?;
+    let attenuation = t.numbers::<3>("attenuation")?;
+    match (falloff, attenuation) {
+        (Some((_, line)), Some(_)) => Err(SceneError::at(line,
+            "a light takes either `falloff` or `attenuation`, not both".to_string())),
+        (None, None) => Ok(Falloff::InverseSquare),
+        (Some((f, line)), None) => match f.as_str() {
+            "none" => Ok(Falloff::None),
+            "inverse_square" => Ok(Falloff::InverseSquare),
+            other => Err(SceneError::at(line, format!(
+                "unknown falloff \"{}\", expected \"none\" or \"inverse_square\"", other))),
+        },
+        (None, Some(([c, l, q], line))) => Falloff::attenuation(c, l, q).ok_or_else(|| SceneError::at(line,
+            "`attenuation` terms must not be negative nor all zero".to_string())),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Num(f32),
+    Str(String),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Num(_) => "a number",
+            Value::Str(_) => "a string",
+            Value::Bool(_) => "a boolean",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+struct Entry {
+    key: String,
+    value: Value,
+    line: usize,
+    used: bool,
+}
+
+// the arrays and the line they start on
+type Rows<const N: usize> = (Vec<[f32; N]>, usize);
+
+struct Table {
+    name: String,
+    array: bool,
+    line: usize,
+    entries: Vec<Entry>,
+}
+
+impl Table {
+    fn has(&self, key: &str) -> bool {
+        self.entries.iter().any(|e| e.key == key)
+    }
+
+    fn take(&mut self, key: &str) -> Option<(Value, usize)> {
+        self.entries.iter_mut()
+            .find(|e| e.key == key)
+            .map(|e| {
+                e.used = true;
+                (e.value.clone(), e.line)
+            })
+    }
+
+    fn missing(&self, key: &str) -> SceneError {
+        SceneError::at(self.line, format!("missing `{}` in [{}]", key, self.name))
+    }
+
+    fn num(&mut self, key: &str) -> Result<Option<(f32, usize)>, SceneError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some((Value::Num(n), line)) => Ok(Some((n, line))),
+            Some((v, line)) => Err(SceneError::at(line,
+                format!("`{}` must be a number, got {}", key, v.kind()))),
+        }
+    }
+
+    fn require_num(&mut self, key: &str) -> Result<(f32, usize), SceneError> {
+        self.num(key)?.ok_or_else(|| self.missing(key))
+    }
+
+    fn positive(&mut self, key: &str) -> Result<Option<f32>, SceneError> {
+        match self.num(key)? {
+            Some((n, line)) if n <= 0.0 => Err(SceneError::at(line,
+                format!("`{}` must be positive, got {}", key, n))),
+            other => Ok(other.map(|(n, _)| n)),
+        }
+    }
+
+    fn require_positive(&mut self, key: &str) -> Result<f32, SceneError> {
+        self.positive(key)?.ok_or_else(|| self.missing(key))
+    }
+
+    fn non_negative(&mut self, key: &str) -> Result<Option<f32>, SceneError> {
+        match self.num(key)? {
+            Some((n, line)) if n < 0.0 => Err(SceneError::at(line,
+                format!("`{}` must not be negative, got {}", key, n))),
+            other => Ok(other.map(|(n, _)| n)),
+        }
+    }
+
+    fn require_non_negative(&mut self, key: &str) -> Result<f32, SceneError> {
+        self.non_negative(key)?.ok_or_else(|| self.missing(key))
+    }
+
+    fn str(&mut self, key: &str) -> Result<Option<(String, usize)>, SceneError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some((Value::Str(s), line)) => Ok(Some((s, line))),
+            Some((v, line)) => Err(SceneError::at(line,
+                format!("`{}` must be a string, got {}", key, v.kind()))),
+        }
+    }
+
+    fn require_str(&mut self, key: &str) -> Result<(String, usize), SceneError> {
+        self.str(key)?.ok_or_else(|| self.missing(key))
+    }
+
+    fn numbers<const N: usize>(&mut self, key: &str) -> Result<Option<([f32; N], usize)>, SceneError> {
+        let (value, line) = match self.take(key) {
+            None => return Ok(None),
+            Some(v) => v,
+        };
+        let bad = || SceneError::at(line, format!("`{}` must be an array of {} numbers", key, N));
+        let items = match value {
+            Value::Array(items) if items.len() == N => items,
+            _ => return Err(bad()),
+        };
+        let mut out = [0.0; N];
+        for (o, item) in out.iter_mut().zip(items) {
+            match item {
+                Value::Num(n) => *o = n,
+                _ => return Err(bad()),
+            }
+        }
+        Ok(Some((out, line)))
+    }
+
+    // an array of arrays of N numbers
+    fn rows<const N: usize>(&mut self, key: &str) -> Result<Option<Rows<N>>, SceneError> {
+        let (value, line) = match self.take(key) {
+            None => return Ok(None),
+            Some(v) => v,
+        };
+        let bad = || SceneError::at(line, format!("`{}` must be an array of arrays of {} numbers", key, N));
+        let items = match value {
+            Value::Array(items) => items,
+            _ => return Err(bad()),
+        };
+        let mut out = Vec::with_capacity(items.len());
+        for item in items {
+            let row = match item {
+                Value::Array(row) if row.len() == N => row,
+                _ => return Err(bad()),
+            };
+            let mut r = [0.0; N];
+            for (o, v) in r.iter_mut().zip(row) {
+                match v {
+                    Value::Num(n) => *o = n,
+                    _ => return Err(bad()),
+                }
+            }
+            out.push(r);
+        }
+        Ok(Some((out, line)))
+    }
+
+    fn vec3(&mut self, key: &str) -> Result<Option<Vec3<f32>>, SceneError> {
+        Ok(self.numbers::<3>(key)?.map(|([x, y, z], _)| Vec3::new(x, y, z)))
+    }
+
+    fn require_vec3(&mut self, key: &str) -> Result<Vec3<f32>, SceneError> {
+        self.vec3(key)?.ok_or_else(|| self.missing(key))
+    }
+
+    fn color(&mut self, key: &str) -> Result<Option<RGB>, SceneError> {
+        let ([r, g, b], line) = match self.numbers::<3>(key)? {
+            None => return Ok(None),
+            Some(v) => v,
+        };
+        for c in [r, g, b].iter() {
+            if *c < 0.0 || *c > 255.0 || c.fract() != 0.0 {
+                return Err(SceneError::at(line,
+                    format!("`{}` components must be integers in 0..=255, got {}", key, c)));
+            }
+        }
+        Ok(Some(RGB::new(r as u8, g as u8, b as u8)))
+    }
+
+    // reject keys nobody asked for, those are most likely typos
+    fn finish(&self) -> Result<(), SceneError> {
+        match self.entries.iter().find(|e| !e.used) {
+            Some(e) => Err(SceneError::at(e.line, format!("unknown key `{}` in [{}]", e.key, self.name))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn parse_tables(text: &str) -> Result<Vec<Table>, SceneError> {
+    let mut tables = Vec::<Table>::new();
+
+    let mut lines = text.lines().enumerate();
+    while let Some((n, raw)) = lines.next() {
+        let line = n + 1;
+        let content = strip_comment(raw).trim();
+        if content.is_empty() {
+            continue;
+        }
+
+        if content.starts_with('[') {
+            let (inner, array) = if content.starts_with("[[") && content.ends_with("]]") {
+                (&content[2..content.len() - 2], true)
+            } else if content.ends_with(']') {
+                (&content[1..content.len() - 1], false)
+            } else {
+                return Err(SceneError::at(line, format!("malformed table header `{}`", content)));
+            };
+            let name = inner.trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(SceneError::at(line, format!("bad table name `{}`", name)));
+            }
+            tables.push(Table {
+                name: name.to_string(),
+                array,
+                line,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let eq = content.find('=')
+            .ok_or_else(|| SceneError::at(line, format!("expected `key = value`, got `{}`", content)))?;
+        let key = content[..eq].trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(SceneError::at(line, format!("bad key `{}`", key)));
+        }
+        // arrays may go on over the next lines until their brackets close
+        let mut text = content[eq + 1..].trim().to_string();
+        while open_brackets(&text) > 0 {
+            match lines.next() {
+                Some((_, more)) => {
+                    text.push(' ');
+                    text.push_str(strip_comment(more).trim());
+                }
+                None => return Err(SceneError::at(line, format!("unclosed array in `{}`", key))),
+            }
+        }
+        let value = parse_value(&text, line)?;
+
+        let table = tables.last_mut()
+            .ok_or_else(|| SceneError::at(line, format!("`{}` is outside of any table", key)))?;
+        if table.entries.iter().any(|e| e.key == key) {
+            return Err(SceneError::at(line, format!("duplicate key `{}`", key)));
+        }
+        table.entries.push(Entry {
+            key: key.to_string(),
+            value,
+            line,
+            used: false,
+        });
+    }
+
+    Ok(tables)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_str = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_str => escaped = true,
+            '"' => in_str = !in_str,
+            '#' if !in_str => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn open_brackets(text: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_str = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_str => escaped = true,
+            '"' => in_str = !in_str,
+            '[' if !in_str => depth += 1,
+            ']' if !in_str => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+fn parse_value(text: &str, line: usize) -> Result<Value, SceneError> {
+    let mut chars = text.chars().peekable();
+    let value = parse_item(&mut chars, line)?;
+    skip_ws(&mut chars);
+    if let Some(c) = chars.next() {
+        return Err(SceneError::at(line, format!("unexpected `{}` after value", c)));
+    }
+    Ok(value)
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_ws(chars: &mut Chars) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_item(chars: &mut Chars, line: usize) -> Result<Value, SceneError> {
+    skip_ws(chars);
+    match chars.peek() {
+        None => Err(SceneError::at(line, "missing value".to_string())),
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            loop {
+                skip_ws(chars);
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    return Ok(Value::Array(items));
+                }
+                items.push(parse_item(chars, line)?);
+                skip_ws(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => return Ok(Value::Array(items)),
+                    _ => return Err(SceneError::at(line, "expected `,` or `]` in array".to_string())),
+                }
+            }
+        }
+        Some('"') => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    None => return Err(SceneError::at(line, "unterminated string".to_string())),
+                    Some('"') => return Ok(Value::Str(s)),
+                    Some('\\') => match chars.next() {
+                        Some('"') => s.push('"'),
+                        Some('\\') => s.push('\\'),
+                        Some('n') => s.push('\n'),
+                        Some('t') => s.push('\t'),
+                        _ => return Err(SceneError::at(line, "bad escape in string".to_string())),
+                    },
+                    Some(c) => s.push(c),
+                }
+            }
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ',' || c == ']' || c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            match word.as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => match word.parse::<f32>() {
+                    Ok(n) if n.is_finite() => Ok(Value::Num(n)),
+                    _ => Err(SceneError::at(line, format!("bad value `{}`", word))),
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,597 @@
+// Analytic primitives next to the sphere : boxes, capped cylinders and cones,
+// disks and tori, and instances placing any shape with a transform.
+//
+// Each one is described in its own frame (w along its axis) and rays are
+// brought into it before being intersected. Hits are found from inside as
+// well, so the closed shapes work with mirrors and glass too.
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::III_vector::{Ray, Transform, Vec3};
+use crate::bvh::Aabb;
+use crate::objects::{HitRecord, Shape, SurfaceOptions, EPSILON};
+
+#[derive(Clone, Copy)]
+struct Frame {
+    origin: Vec3<f32>,
+    u: Vec3<f32>,
+    v: Vec3<f32>,
+    w: Vec3<f32>,
+}
+
+impl Frame {
+    // `w` must be a unit vector, `u` and `v` are picked around it
+    fn around(origin: Vec3<f32>, w: Vec3<f32>) -> Frame {
+        let (u, v) = w.orthonormal();
+        Frame { origin, u, v, w }
+    }
+
+    fn point(&self, p: Vec3<f32>) -> Vec3<f32> {
+        self.dir(p - self.origin)
+    }
+
+    fn dir(&self, d: Vec3<f32>) -> Vec3<f32> {
+        Vec3::new(d * self.u, d * self.v, d * self.w)
+    }
+
+    fn world_dir(&self, l: Vec3<f32>) -> Vec3<f32> {
+        self.u.scale(l.x) + self.v.scale(l.y) + self.w.scale(l.z)
+    }
+}
+
+// distance, normal in the local frame and uv
+type LocalHit = (f32, Vec3<f32>, (f32, f32));
+
+// the nearest of the candidate hits far enough from the origin of the ray
+fn nearest(found: &[Option<LocalHit>]) -> Option<LocalHit> {
+    found.iter()
+        .flatten()
+        .filter(|h| h.0 > EPSILON)
+        .fold(None, |best: Option<LocalHit>, h| match best {
+            Some(b) if b.0 <= h.0 => Some(b),
+            _ => Some(*h),
+        })
+}
+
+// the hit record of a local hit, `root` and `dir` being the world ray with
+// a unit `dir`
+fn record(f: &Frame, root: Vec3<f32>, dir: Vec3<f32>, hit: LocalHit, m: SurfaceOptions)
+    -> HitRecord {
+    let (t, n, uv) = hit;
+    HitRecord::new(t, root + dir.scale(t), f.world_dir(n).norm(), dir, uv, m)
+}
+
+// roots of a t^2 + b t + c, the smaller first
+fn quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        return Some((-c / b, -c / b));
+    }
+    quadratic_roots(a, b, c, b * b - 4.0 * a * c)
+}
+
+// the same from its discriminant b^2 - 4 a c, for the callers that can
+// compute it more precisely
+pub(crate) fn quadratic_roots(a: f32, b: f32, c: f32, disc: f32) -> Option<(f32, f32)> {
+    if disc.is_nan() || disc < 0.0 {
+        return None;
+    }
+    // avoids the cancellation of -b + sqrt(disc) when both are close
+    let q = -0.5 * (b + b.signum() * disc.sqrt());
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+// a flat round cap of `radius` at height `z` of the local frame, facing `n`
+fn cap(o: Vec3<f32>, d: Vec3<f32>, z: f32, radius: f32, n: Vec3<f32>) -> Option<LocalHit> {
+    if d.z.abs() < 1e-8 {
+        return None;
+    }
+    let t = (z - o.z) / d.z;
+    let (x, y) = (o.x + t * d.x, o.y + t * d.y);
+    if x * x + y * y > radius * radius {
+        return None;
+    }
+    Some((t, n, (0.5 + 0.5 * x / radius, 0.5 + 0.5 * y / radius)))
+}
+
+// the box around a circle of `radius` facing the unit `w`, grown by `pad`
+fn ring_bounds(center: Vec3<f32>, w: Vec3<f32>, radius: f32, pad: f32) -> Aabb {
+    let e = |a: f32| radius * (1.0 - a * a).max(0.0).sqrt() + pad;
+    let e = Vec3::new(e(w.x), e(w.y), e(w.z));
+    Aabb::new(center - e, center + e)
+}
+
+// around the axis, in [0, 1)
+fn turn(x: f32, y: f32) -> f32 {
+    0.5 + y.atan2(x) / (2.0 * PI)
+}
+
+// a box with its faces along three orthogonal axes ; the uv of a hit are
+// its coordinates across the face, in [0, 1]
+#[derive(Clone, Copy)]
+pub struct Cuboid {
+    frame: Frame,
+    half: [f32; 3],
+    opt: SurfaceOptions,
+}
+
+impl Cuboid {
+    // aligned with the world axes, between the corners `min` and `max`
+    pub fn new(min: Vec3<f32>, max: Vec3<f32>, o: SurfaceOptions) -> Option<Cuboid> {
+        let center = (min + max).scale(0.5);
+        let size = max - min;
+        Cuboid::oriented(center, size, Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), o)
+    }
+
+    // `size` is measured along `x_axis`, `y_axis` and their cross product ;
+    // `y_axis` is straightened to be orthogonal to `x_axis`
+    pub fn oriented(center: Vec3<f32>, size: Vec3<f32>, x_axis: Vec3<f32>, y_axis: Vec3<f32>, o: SurfaceOptions)
+        -> Option<Cuboid> {
+        if !(size.x > 0.0 && size.y > 0.0 && size.z > 0.0) {
+            return None;
+        }
+        let u = x_axis.norm();
+        let w = Vec3::prod(u, y_axis);
+        if !(u.len().is_finite() && w.len() > 1e-6) {
+            return None;
+        }
+        let w = w.norm();
+        let frame = Frame { origin: center, u, v: Vec3::prod(w, u), w };
+        Some(Cuboid {
+            frame,
+            half: [size.x / 2.0, size.y / 2.0, size.z / 2.0],
+            opt: o,
+        })
+    }
+}
+
+impl Shape for Cuboid {
+    fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+        let dir = dir.norm();
+        let o = self.frame.point(root);
+        let d = self.frame.dir(dir);
+        let (o, d) = ([o.x, o.y, o.z], [d.x, d.y, d.z]);
+
+        // slabs : where the ray gets in and out of the box, and through which axis
+        let (mut t_in, mut t_out) = (f32::NEG_INFINITY, f32::INFINITY);
+        let (mut axis_in, mut axis_out) = (0, 0);
+        for i in 0..3 {
+            if d[i].abs() < 1e-12 {
+                if o[i].abs() > self.half[i] {
+                    return None;
+                }
+                continue;
+            }
+            let (mut t0, mut t1) = ((-self.half[i] - o[i]) / d[i], (self.half[i] - o[i]) / d[i]);
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_in {
+                t_in = t0;
+                axis_in = i;
+            }
+            if t1 < t_out {
+                t_out = t1;
+                axis_out = i;
+            }
+        }
+        if t_in > t_out {
+            return None;
+        }
+
+        let face = |t: f32, i: usize| {
+            let p = [o[0] + t * d[0], o[1] + t * d[1], o[2] + t * d[2]];
+            let mut n = [0.0; 3];
+            n[i] = p[i].signum();
+            let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+            let uv = (0.5 + 0.5 * p[j] / self.half[j], 0.5 + 0.5 * p[k] / self.half[k]);
+            (t, Vec3::new(n[0], n[1], n[2]), uv)
+        };
+        let hit = nearest(&[Some(face(t_in, axis_in)), Some(face(t_out, axis_out))])?;
+        Some(record(&self.frame, root, dir, hit, self.opt))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let f = &self.frame;
+        let [x, y, z] = self.half;
+        let mut b = Aabb::empty();
+        for &(i, j, k) in &[(-1.0, -1.0, -1.0), (1.0, -1.0, -1.0), (-1.0, 1.0, -1.0), (1.0, 1.0, -1.0),
+            (-1.0, -1.0, 1.0), (1.0, -1.0, 1.0), (-1.0, 1.0, 1.0), (1.0, 1.0, 1.0)] {
+            b = b.grow(f.origin + f.world_dir(Vec3::new(i * x, j * y, k * z)));
+        }
+        Some(b)
+    }
+}
+
+// a tube closed by two disks, around the line from `base` to `top` ; the uv
+// of its side are the turn around the axis and the height, in [0, 1]
+#[derive(Clone, Copy)]
+pub struct Cylinder {
+    frame: Frame,
+    height: f32,
+    radius: f32,
+    opt: SurfaceOptions,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3<f32>, top: Vec3<f32>, r: f32, o: SurfaceOptions) -> Option<Cylinder> {
+        let h = (top - base).len();
+        if !(h > 0.0 && r > 0.0) {
+            return None;
+        }
+        Some(Cylinder {
+            frame: Frame::around(base, (top - base).norm()),
+            height: h,
+            radius: r,
+            opt: o,
+        })
+    }
+}
+
+impl Shape for Cylinder {
+    fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+        let dir = dir.norm();
+        let o = self.frame.point(root);
+        let d = self.frame.dir(dir);
+
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+        let side = |t: f32| {
+            let p = o + d.scale(t);
+            if p.z >= 0.0 && p.z <= self.height {
+                Some((t, Vec3::new(p.x, p.y, 0.0), (turn(p.x, p.y), p.z / self.height)))
+            } else {
+                None
+            }
+        };
+        // a ray along the axis never meets the side
+        let (t0, t1) = if a > 1e-12 { quadratic(a, b, c).unwrap_or((-1.0, -1.0)) } else { (-1.0, -1.0) };
+
+        let hit = nearest(&[
+            side(t0),
+            side(t1),
+            cap(o, d, 0.0, self.radius, Vec3::new(0.0, 0.0, -1.0)),
+            cap(o, d, self.height, self.radius, Vec3::new(0.0, 0.0, 1.0)),
+        ])?;
+        Some(record(&self.frame, root, dir, hit, self.opt))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let (o, w) = (self.frame.origin, self.frame.w);
+        let base = ring_bounds(o, w, self.radius, 0.0);
+        Some(base.union(ring_bounds(o + w.scale(self.height), w, self.radius, 0.0)))
+    }
+}
+
+// a round `base` of `radius` narrowing to the `apex`; uv as on a cylinder
+#[derive(Clone, Copy)]
+pub struct Cone {
+    frame: Frame,
+    height: f32,
+    radius: f32,
+    opt: SurfaceOptions,
+}
+
+impl Cone {
+    pub fn new(base: Vec3<f32>, apex: Vec3<f32>, r: f32, o: SurfaceOptions) -> Option<Cone> {
+        let h = (apex - base).len();
+        if !(h > 0.0 && r > 0.0) {
+            return None;
+        }
+        Some(Cone {
+            frame: Frame::around(base, (apex - base).norm()),
+            height: h,
+            radius: r,
+            opt: o,
+        })
+    }
+}
+
+impl Shape for Cone {
+    fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+        let dir = dir.norm();
+        let o = self.frame.point(root);
+        let d = self.frame.dir(dir);
+
+        // x^2 + y^2 = (k (h - z))^2 with k the slope of the side
+        let k2 = (self.radius / self.height).powi(2);
+        let hz = self.height - o.z;
+        let a = d.x * d.x + d.y * d.y - k2 * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y + k2 * hz * d.z);
+        let c = o.x * o.x + o.y * o.y - k2 * hz * hz;
+        // the equation also holds on the mirrored cone above the apex
+        let side = |t: f32| {
+            let p = o + d.scale(t);
+            if p.z >= 0.0 && p.z <= self.height {
+                Some((t, Vec3::new(p.x, p.y, k2 * (self.height - p.z)), (turn(p.x, p.y), p.z / self.height)))
+            } else {
+                None
+            }
+        };
+        let (t0, t1) = quadratic(a, b, c).unwrap_or((-1.0, -1.0));
+
+        let hit = nearest(&[
+            side(t0),
+            side(t1),
+            cap(o, d, 0.0, self.radius, Vec3::new(0.0, 0.0, -1.0)),
+        ])?;
+        Some(record(&self.frame, root, dir, hit, self.opt))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let (o, w) = (self.frame.origin, self.frame.w);
+        Some(ring_bounds(o, w, self.radius, 0.0).grow(o + w.scale(self.height)))
+    }
+}
+
+// flat and round, both sides look the same ; the uv map the disk into the
+// unit square
+#[derive(Clone, Copy)]
+pub struct Disk {
+    frame: Frame,
+    radius: f32,
+    opt: SurfaceOptions,
+}
+
+impl Disk {
+    pub fn new(center: Vec3<f32>, normal: Vec3<f32>, r: f32, o: SurfaceOptions) -> Option<Disk> {
+        if !(normal.len() > 0.0 && r > 0.0) {
+            return None;
+        }
+        Some(Disk {
+            frame: Frame::around(center, normal.norm()),
+            radius: r,
+            opt: o,
+        })
+    }
+}
+
+impl Shape for Disk {
+    fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+        let dir = dir.norm();
+        let o = self.frame.point(root);
+        let d = self.frame.dir(dir);
+        let hit = nearest(&[cap(o, d, 0.0, self.radius, Vec3::new(0.0, 0.0, 1.0))])?;
+        Some(record(&self.frame, root, dir, hit, self.opt))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(ring_bounds(self.frame.origin, self.frame.w, self.radius, 0.0))
+    }
+}
+
+// the tube of radius `minor` around the circle of radius `major` ; the uv
+// are the turns around the axis and around the tube
+#[derive(Clone, Copy)]
+pub struct Torus {
+    frame: Frame,
+    major: f32,
+    minor: f32,
+    opt: SurfaceOptions,
+}
+
+impl Torus {
+    // `minor` must be smaller than `major`, the hole stays open
+    pub fn new(center: Vec3<f32>, axis: Vec3<f32>, major: f32, minor: f32, o: SurfaceOptions) -> Option<Torus> {
+        if !(axis.len() > 0.0 && minor > 0.0 && minor < major) {
+            return None;
+        }
+        Some(Torus {
+            frame: Frame::around(center, axis.norm()),
+            major,
+            minor,
+            opt: o,
+        })
+    }
+}
+
+impl Shape for Torus {
+    fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+        let dir = dir.norm();
+        let o = self.frame.point(root);
+        let d = self.frame.dir(dir);
+
+        // start from the bounding sphere, the quartic loses precision when
+        // the ray comes from far away
+        let bound = self.major + self.minor;
+        let (near, far) = quadratic(1.0, 2.0 * (o * d), o * o - bound * bound)?;
+        if far < EPSILON {
+            return None;
+        }
+        let shift = near.max(0.0);
+        let s = o + d.scale(shift);
+
+        let (ox, oy, oz) = (s.x as f64, s.y as f64, s.z as f64);
+        let (dx, dy, dz) = (d.x as f64, d.y as f64, d.z as f64);
+        let (rr, r2) = ((self.major as f64).powi(2), (self.minor as f64).powi(2));
+        let m = ox * ox + oy * oy + oz * oz;
+        let n = ox * dx + oy * dy + oz * dz;
+        let k = m + rr - r2;
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) along p = s + t d
+        let coeffs = [
+            k * k - 4.0 * rr * (ox * ox + oy * oy),
+            4.0 * n * k - 8.0 * rr * (ox * dx + oy * dy),
+            4.0 * n * n + 2.0 * k - 4.0 * rr * (dx * dx + dy * dy),
+            4.0 * n,
+            1.0,
+        ];
+
+        let mut found = [None; 4];
+        for (slot, t) in found.iter_mut().zip(solve_quartic(coeffs)) {
+            let t = polish(&coeffs, t);
+            let p = s + d.scale(t as f32);
+            let ring = (p.x * p.x + p.y * p.y).sqrt();
+            let n = if ring > 0.0 {
+                p - Vec3::new(p.x, p.y, 0.0).scale(self.major / ring)
+            } else {
+                p
+            };
+            let uv = (turn(p.x, p.y), 0.5 + p.z.atan2(ring - self.major) / (2.0 * PI));
+            *slot = Some((t as f32 + shift, n, uv));
+        }
+
+        let hit = nearest(&found)?;
+        Some(record(&self.frame, root, dir, hit, self.opt))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(ring_bounds(self.frame.origin, self.frame.w, self.major, self.minor))
+    }
+}
+
+// a shape moved, turned or stretched by a transform ; rays are brought into
+// the space of the shape and its normals back into the world, so a scaled
+// sphere is an ellipsoid. Shapes behind an Arc can be placed many times
+// without being copied.
+#[derive(Clone)]
+pub struct Instance {
+    shape: Arc<dyn Shape>,
+    transform: Transform,
+}
+
+impl Instance {
+    pub fn new<S: Shape + 'static>(shape: S, t: Transform) -> Instance {
+        Instance::shared(Arc::new(shape), t)
+    }
+
+    pub fn shared(shape: Arc<dyn Shape>, t: Transform) -> Instance {
+        Instance { shape, transform: t }
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+}
+
+impl Shape for Instance {
+    fn intersect(&self, root: Vec3<f32>, dir: Vec3<f32>) -> Option<HitRecord> {
+        let dir = dir.norm();
+        let d = self.transform.inv_dir(dir);
+        // object space units per world unit along the ray
+        let k = d.len();
+        let hit = self.shape.intersect(self.transform.inv_point(root), d.scale(1.0 / k))?;
+        let t = hit.t / k;
+        let outward = if hit.front_face { hit.normal } else { hit.normal.scale(-1.0) };
+        Some(HitRecord::new(t, root + dir.scale(t), self.transform.normal(outward), dir, hit.uv, hit.material))
+    }
+
+    fn transmit(&self, hit: &HitRecord, dir: Vec3<f32>) -> Option<Ray<f32>> {
+        let d = self.transform.inv_dir(dir.norm());
+        let local = HitRecord {
+            t: hit.t * d.len(),
+            point: self.transform.inv_point(hit.point),
+            normal: self.transform.inv_normal(hit.normal),
+            ..*hit
+        };
+        let out = self.shape.transmit(&local, d.norm())?;
+        Some(Ray {
+            root: self.transform.point(out.root),
+            dir: self.transform.dir(out.dir).norm(),
+        })
+    }
+
+    // the box of the shape, turned, holds it but may be larger than needed
+    fn bounds(&self) -> Option<Aabb> {
+        let inner = self.shape.bounds()?;
+        Some(Aabb::from_points(&inner.corners().map(|c| self.transform.point(c))))
+    }
+}
+
+// a few Newton steps on a root of the polynomial c0 + c1 t + ... + c4 t^4
+fn polish(c: &[f64; 5], mut t: f64) -> f64 {
+    for _ in 0..3 {
+        let f = (((c[4] * t + c[3]) * t + c[2]) * t + c[1]) * t + c[0];
+        let df = ((4.0 * c[4] * t + 3.0 * c[3]) * t + 2.0 * c[2]) * t + c[1];
+        if df.abs() < 1e-12 {
+            break;
+        }
+        t -= f / df;
+    }
+    t
+}
+
+fn is_zero(x: f64) -> bool {
+    x.abs() < 1e-9
+}
+
+// real roots of c0 + c1 x + c2 x^2, c2 != 0
+fn solve_quadric(c: [f64; 3]) -> Vec<f64> {
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let disc = p * p - q;
+    if is_zero(disc) {
+        vec![-p]
+    } else if disc < 0.0 {
+        Vec::new()
+    } else {
+        let s = disc.sqrt();
+        vec![s - p, -s - p]
+    }
+}
+
+// real roots of c0 + c1 x + c2 x^2 + c3 x^3, c3 != 0 (Cardano)
+fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    let (a, b, cc) = (c[2] / c[3], c[1] / c[3], c[0] / c[3]);
+    // x = y - a/3 leaves y^3 + 3 p y + 2 q = 0
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + cc) / 2.0;
+    let cb_p = p * p * p;
+    let disc = q * q + cb_p;
+
+    let mut roots = if is_zero(disc) {
+        if is_zero(q) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if disc < 0.0 {
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        let third = std::f64::consts::PI / 3.0;
+        vec![t * phi.cos(), -t * (phi + third).cos(), -t * (phi - third).cos()]
+    } else {
+        let s = disc.sqrt();
+        vec![(s - q).cbrt() - (s + q).cbrt()]
+    };
+    for r in roots.iter_mut() {
+        *r -= a / 3.0;
+    }
+    roots
+}
+
+// real roots of c0 + c1 x + ... + c4 x^4, c4 != 0 (Ferrari)
+fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    let (a, b, cc, d) = (c[3] / c[4], c[2] / c[4], c[1] / c[4], c[0] / c[4]);
+    // x = y - a/4 leaves y^4 + p y^2 + q y + r = 0
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + cc;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * cc / 4.0 + d;
+
+    let mut roots = if is_zero(r) {
+        let mut roots = solve_cubic([q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else {
+        // one root of the resolvent cubic splits it into two quadrics
+        let z = solve_cubic([r * p / 2.0 - q * q / 8.0, -r, -p / 2.0, 1.0])[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if is_zero(u) { 0.0 } else if u > 0.0 { u.sqrt() } else { return Vec::new() };
+        let v = if is_zero(v) { 0.0 } else if v > 0.0 { v.sqrt() } else { return Vec::new() };
+        let mut roots = solve_quadric([z - u, if q < 0.0 { -v } else { v }, 1.0]);
+        roots.extend(solve_quadric([z + u, if q < 0.0 { v } else { -v }, 1.0]));
+        roots
+    };
+    for x in roots.iter_mut() {
+        *x -= a / 4.0;
+    }
+    roots
+}
@@ -0,0 +1,137 @@
+// Post process between the linear radiance of the framebuffer and 8 bit
+// pixels : exposure, then a tone mapping operator squeezing [0, inf) into
+// [0, 1], then the transfer function of the output.
+
+use crate::hdr::RGBf;
+use crate::PPM::RGB;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operator {
+    // cut everything above 1.0, the look the tracer always had
+    Clamp,
+    // L / (1 + L) on the luminance, keeps the hue ; `white` is the smallest
+    // luminance mapped to pure white
+    Reinhard { white: f32 },
+    // Narkowicz's fit of the ACES filmic curve
+    Aces,
+    // Hable's curve from Uncharted 2
+    Filmic,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Transfer {
+    Linear,
+    Srgb,
+    Gamma(f32),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ToneMap {
+    // in stops, every +1 doubles the brightness
+    pub exposure: f32,
+    pub operator: Operator,
+    pub transfer: Transfer,
+}
+
+impl Default for ToneMap {
+    fn default() -> Self {
+        ToneMap {
+            exposure: 0.0,
+            operator: Operator::Clamp,
+            transfer: Transfer::Linear,
+        }
+    }
+}
+
+impl Operator {
+    pub fn from_name(name: &str) -> Option<Operator> {
+        match name.to_ascii_lowercase().as_str() {
+            "clamp" | "none" => Some(Operator::Clamp),
+            "reinhard" => Some(Operator::Reinhard { white: f32::INFINITY }),
+            "aces" => Some(Operator::Aces),
+            "filmic" => Some(Operator::Filmic),
+            _ => None,
+        }
+    }
+}
+
+impl Transfer {
+    pub fn from_name(name: &str) -> Option<Transfer> {
+        match name.to_ascii_lowercase().as_str() {
+            "linear" => Some(Transfer::Linear),
+            "srgb" => Some(Transfer::Srgb),
+            other => match other.parse::<f32>() {
+                Ok(g) if g > 0.0 && g.is_finite() => Some(Transfer::Gamma(g)),
+                _ => None,
+            },
+        }
+    }
+}
+
+fn luminance(c: RGBf) -> f32 {
+    0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+}
+
+fn aces(x: f32) -> f32 {
+    let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+    (x * (a * x + b)) / (x * (c * x + d) + e)
+}
+
+fn hable(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl ToneMap {
+    pub fn new(exposure: f32, operator: Operator, transfer: Transfer) -> ToneMap {
+        ToneMap {
+            exposure,
+            operator,
+            transfer,
+        }
+    }
+
+    // linear radiance to display values in [0, 1]
+    pub fn apply(&self, c: RGBf) -> RGBf {
+        let c = c.scale(self.exposure.exp2());
+        let c = RGBf::new(c.r.max(0.0), c.g.max(0.0), c.b.max(0.0));
+
+        let mapped = match self.operator {
+            Operator::Clamp => c,
+            Operator::Reinhard { white } => {
+                let l = luminance(c);
+                if l <= 0.0 {
+                    c
+                } else {
+                    let ld = l * (1.0 + l / (white * white)) / (1.0 + l);
+                    c.scale(ld / l)
+                }
+            }
+            Operator::Aces => RGBf::new(aces(c.r), aces(c.g), aces(c.b)),
+            Operator::Filmic => {
+                // exposure bias and linear white point from the original talk
+                let w = 1.0 / hable(11.2);
+                RGBf::new(hable(2.0 * c.r) * w, hable(2.0 * c.g) * w, hable(2.0 * c.b) * w)
+            }
+        };
+        let mapped = RGBf::new(mapped.r.min(1.0), mapped.g.min(1.0), mapped.b.min(1.0));
+
+        match self.transfer {
+            Transfer::Linear => mapped,
+            Transfer::Srgb => RGBf::new(srgb(mapped.r), srgb(mapped.g), srgb(mapped.b)),
+            Transfer::Gamma(g) => RGBf::new(mapped.r.powf(1.0 / g), mapped.g.powf(1.0 / g), mapped.b.powf(1.0 / g)),
+        }
+    }
+
+    pub fn to_rgb(&self, c: RGBf) -> RGB {
+        self.apply(c).to_rgb()
+    }
+}
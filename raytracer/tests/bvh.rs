@@ -0,0 +1,146 @@
+use raytracer::III_vector::{Transform, Vec3};
+use raytracer::bvh::{Aabb, Bvh};
+use raytracer::objects::{Plane, Scene, Shape, Sphere, TriangleMesh};
+use raytracer::sampling::Rng;
+use raytracer::shapes::{Cone, Cuboid, Cylinder, Disk, Instance, Torus};
+
+mod common;
+use common::{matte, v};
+
+fn random_dir(rng: &mut Rng) -> Vec3<f32> {
+    loop {
+        let d = v(rng.next_f32() - 0.5, rng.next_f32() - 0.5, rng.next_f32() - 0.5);
+        if d.len() > 0.1 {
+            return d.norm();
+        }
+    }
+}
+
+#[test]
+fn boxes_are_hit_on_their_slabs() {
+    let b = Aabb::new(v(3.0, 1.0, 1.0), v(1.0, -1.0, -1.0));
+    let inv = |d: Vec3<f32>| v(1.0 / d.x, 1.0 / d.y, 1.0 / d.z);
+    assert_eq!(b.hit(v(0.0, 0.0, 0.0), inv(v(1.0, 0.0, 0.0)), f32::INFINITY), Some(1.0));
+    assert_eq!(b.hit(v(2.0, 0.0, 0.0), inv(v(1.0, 0.0, 0.0)), f32::INFINITY), Some(0.0));
+    assert_eq!(b.hit(v(0.0, 0.0, 0.0), inv(v(1.0, 0.0, 0.0)), 0.5), None);
+    assert_eq!(b.hit(v(0.0, 2.0, 0.0), inv(v(1.0, 0.0, 0.0)), f32::INFINITY), None);
+    assert_eq!(b.hit(v(0.0, 0.0, 0.0), inv(v(-1.0, 0.0, 0.0)), f32::INFINITY), None);
+
+    assert!(Aabb::empty().is_empty());
+    assert_eq!(Aabb::empty().area(), 0.0);
+    assert_eq!(b.area(), 24.0);
+    let u = b.union(Aabb::new(v(5.0, 0.0, 0.0), v(5.0, 0.0, 0.0)));
+    assert_eq!((u.min.x, u.max.x), (1.0, 5.0));
+}
+
+#[test]
+fn bvh_finds_what_a_loop_finds() {
+    let mut rng = Rng::new(7);
+    let spheres: Vec<Sphere> = (0..500)
+        .map(|_| {
+            let c = v(rng.next_f32() * 10.0, rng.next_f32() * 10.0, rng.next_f32() * 10.0);
+            Sphere::new(c, 0.1 + rng.next_f32() * 0.5, matte()).unwrap()
+        })
+        .collect();
+    let boxes: Vec<Aabb> = spheres.iter().map(|s| Shape::bounds(s).unwrap()).collect();
+    let bvh = Bvh::new(&boxes);
+    assert_eq!(bvh.len(), 500);
+    // far from a list
+    assert!(bvh.depth() < 30);
+
+    for _ in 0..2000 {
+        let root = v(rng.next_f32() * 12.0 - 1.0, rng.next_f32() * 12.0 - 1.0, rng.next_f32() * 12.0 - 1.0);
+        let dir = random_dir(&mut rng);
+        let expected = spheres.iter().enumerate()
+            .filter_map(|(i, s)| Shape::ray_intersect(s, root, dir).map(|t| (i, t)))
+            .fold(None, |best: Option<(usize, f32)>, (i, t)| match best {
+                Some((_, b)) if b <= t => best,
+                _ => Some((i, t)),
+            });
+        let found = bvh.nearest(root, dir, |i| Shape::ray_intersect(&spheres[i], root, dir));
+        assert_eq!(found, expected);
+    }
+    assert!(Bvh::new(&[]).nearest(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0), |_| Some(1.0)).is_none());
+}
+
+#[test]
+fn scene_mixes_the_bvh_with_unbounded_planes() {
+    let mut scene = Scene::new();
+    scene.add_obj(Plane::new(v(0.0, 0.0, -1.0), v(0.0, 0.0, 1.0), matte()).unwrap());
+    for i in 0..100 {
+        scene.add_obj(Sphere::new(v(2.0 + i as f32, 0.0, 0.0), 0.4, matte()).unwrap());
+    }
+    scene.add_obj(Plane::new(v(50.0, 0.0, 0.0), v(-1.0, 0.0, 0.0), matte()).unwrap());
+
+    assert_eq!(scene.ind_viewing(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), Some(1));
+    assert_eq!(scene.ind_viewing(v(10.5, 0.0, 0.0), v(1.0, 0.0, 0.0)), Some(10));
+    // the wall at x = 50 hides the spheres behind it
+    assert_eq!(scene.ind_viewing(v(49.7, 0.0, 0.0), v(1.0, 0.0, 0.0)), Some(101));
+    assert_eq!(scene.ind_viewing(v(0.0, 0.0, 0.0), v(1.0, 0.0, -1.0)), Some(0));
+    assert_eq!(scene.ind_viewing(v(0.0, 0.0, 0.0), v(0.0, 0.0, 1.0)), None);
+
+    // objects added later are seen as well
+    scene.add_obj(Sphere::new(v(1.0, 0.0, 0.0), 0.2, matte()).unwrap());
+    assert_eq!(scene.ind_viewing(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), Some(102));
+}
+
+#[test]
+fn large_meshes_hit_the_right_face() {
+    // a 100 x 100 grid of squares in the plane x = 3
+    let n = 100;
+    let mut vertices = Vec::new();
+    for j in 0..=n {
+        for i in 0..=n {
+            vertices.push(v(3.0, i as f32 / 10.0 - 5.0, j as f32 / 10.0 - 5.0));
+        }
+    }
+    let mut faces = Vec::new();
+    for j in 0..n {
+        for i in 0..n {
+            let k = j * (n + 1) + i;
+            faces.push([k, k + 1, k + n + 1]);
+            faces.push([k + 1, k + n + 2, k + n + 1]);
+        }
+    }
+    let mesh = TriangleMesh::new(vertices, faces, None, matte()).unwrap();
+    assert_eq!(mesh.len(), 20_000);
+    let b = mesh.bounds().unwrap();
+    assert_eq!((b.min.y, b.max.y, b.min.x, b.max.x), (-5.0, 5.0, 3.0, 3.0));
+
+    let hit = mesh.intersect(v(0.0, 1.23, -2.34), v(1.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - 3.0).abs() < 1e-5);
+    assert!((hit.point.y - 1.23).abs() < 1e-5 && (hit.point.z + 2.34).abs() < 1e-5);
+    assert!(mesh.ray_intersect(v(0.0, 5.5, 0.0), v(1.0, 0.0, 0.0)).is_none());
+}
+
+#[test]
+fn shapes_stay_in_their_bounds() {
+    let t = Transform::rotate(v(1.0, 1.0, 0.0), 30.0).unwrap().then(Transform::translate(v(0.5, 0.0, 0.0)));
+    let shapes: Vec<Box<dyn Shape>> = vec![
+        Box::new(Sphere::new(v(0.0, 0.0, 0.0), 1.0, matte()).unwrap()),
+        Box::new(Cuboid::oriented(v(0.0, 0.0, 0.0), v(1.0, 1.5, 0.5), v(1.0, 1.0, 0.0), v(0.0, 0.0, 1.0), matte()).unwrap()),
+        Box::new(Cylinder::new(v(0.0, 0.0, -0.5), v(0.3, 0.2, 0.7), 0.4, matte()).unwrap()),
+        Box::new(Cone::new(v(0.0, 0.0, -0.5), v(-0.3, 0.2, 0.7), 0.6, matte()).unwrap()),
+        Box::new(Disk::new(v(0.0, 0.0, 0.0), v(1.0, 2.0, 3.0), 0.8, matte()).unwrap()),
+        Box::new(Torus::new(v(0.0, 0.0, 0.0), v(0.0, 1.0, 1.0), 0.7, 0.2, matte()).unwrap()),
+        Box::new(Instance::new(Sphere::new(v(0.0, 0.0, 0.0), 0.5, matte()).unwrap(), t)),
+    ];
+    let mut rng = Rng::new(3);
+    for s in &shapes {
+        let b = s.bounds().unwrap();
+        let mut hits = 0;
+        for _ in 0..500 {
+            let dir = random_dir(&mut rng);
+            let root = dir.scale(-5.0) + v(rng.next_f32() - 0.5, rng.next_f32() - 0.5, rng.next_f32() - 0.5);
+            if let Some(h) = s.intersect(root, dir) {
+                hits += 1;
+                let p = h.point;
+                let e = 1e-4;
+                assert!(p.x >= b.min.x - e && p.y >= b.min.y - e && p.z >= b.min.z - e);
+                assert!(p.x <= b.max.x + e && p.y <= b.max.y + e && p.z <= b.max.z + e);
+            }
+        }
+        assert!(hits > 0);
+    }
+    assert!(Plane::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, 1.0), matte()).unwrap().bounds().is_none());
+}
@@ -0,0 +1,150 @@
+use raytracer::camera::{Camera, Projection};
+use raytracer::render::{render, Settings};
+use raytracer::scene_file::SceneFile;
+use raytracer::III_vector::{Ray, Vec3};
+
+fn v(x: f32, y: f32, z: f32) -> Vec3<f32> {
+    Vec3::new(x, y, z)
+}
+
+fn close(a: Vec3<f32>, b: Vec3<f32>) -> bool {
+    (a - b).len() < 1e-5
+}
+
+fn angle(a: Vec3<f32>, b: Vec3<f32>) -> f32 {
+    (a.norm() * b.norm()).clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+#[test]
+fn center_ray_looks_at_the_target() {
+    let (eye, target) = (v(1.0, 2.0, 3.0), v(4.0, -2.0, 3.5));
+    let cam = Camera::look_at(eye, target, v(0.0, 0.0, 1.0), 60.0, 1.5).unwrap();
+    let ray = cam.ray(0.5, 0.5).unwrap();
+    assert!(close(ray.root, eye));
+    assert!(close(ray.dir.norm(), (target - eye).norm()));
+    assert_eq!(cam.focus_distance(), (target - eye).len());
+
+    // the top and bottom edges are half the field of view away
+    assert!((angle(cam.ray(0.5, 0.0).unwrap().dir, target - eye) - 30.0).abs() < 1e-3);
+    assert!((angle(cam.ray(0.5, 1.0).unwrap().dir, target - eye) - 30.0).abs() < 1e-3);
+
+    assert!(Camera::look_at(eye, eye, v(0.0, 0.0, 1.0), 60.0, 1.0).is_none());
+    assert!(Camera::look_at(eye, eye + v(0.0, 0.0, 2.0), v(0.0, 0.0, 1.0), 60.0, 1.0).is_none());
+    assert!(Camera::look_at(eye, target, v(0.0, 0.0, 1.0), 180.0, 1.0).is_none());
+}
+
+#[test]
+fn up_orients_the_picture() {
+    let forward = v(1.0, 0.0, 0.0);
+    let top_and_right = |up: Vec3<f32>| {
+        let cam = Camera::look_at(v(0.0, 0.0, 0.0), forward, up, 90.0, 2.0).unwrap();
+        (cam.ray(0.5, 0.0).unwrap().dir, cam.ray(1.0, 0.5).unwrap().dir)
+    };
+
+    // v = 0 is the top of the picture, toward `up`
+    let (top, right) = top_and_right(v(0.0, 0.0, 1.0));
+    assert!(close(top.norm(), v(1.0, 0.0, 1.0).norm()));
+    // x forward and z up leave -y on the right ; twice as wide as high
+    assert!(close(right, v(1.0, -2.0, 0.0)));
+
+    let (top, right) = top_and_right(v(0.0, 0.0, -1.0));
+    assert!(top.z < 0.0 && right.y > 0.0);
+
+    // an `up` leaning along the view only gives the side to turn to
+    let (top, _) = top_and_right(v(0.5, 0.0, 1.0));
+    assert!(close(top.norm(), v(1.0, 0.0, 1.0).norm()));
+    let (top, _) = top_and_right(v(0.0, 1.0, 0.0));
+    assert!(close(top.norm(), v(1.0, 1.0, 0.0).norm()));
+}
+
+// from the origin along +x, +z up : -y is on the right
+fn projected(p: Projection, fov: f32, aspect: f32) -> Camera {
+    Camera::new(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0), v(0.0, 0.0, 1.0), fov, aspect, p).unwrap()
+}
+
+#[test]
+fn orthographic_rays_are_parallel() {
+    let cam = projected(Projection::Orthographic { height: 2.0 }, 0.0, 1.5);
+    for &(x, y) in [(0.0, 0.0), (0.5, 0.5), (1.0, 0.25), (0.3, 1.0)].iter() {
+        assert!(close(cam.ray(x, y).unwrap().dir.norm(), v(1.0, 0.0, 0.0)));
+    }
+    // they start on a plane as wide as the picture
+    assert!(close(cam.ray(0.0, 0.0).unwrap().root, v(0.0, 1.5, 1.0)));
+    assert!(close(cam.ray(1.0, 1.0).unwrap().root, v(0.0, -1.5, -1.0)));
+    assert!(Camera::new(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0), v(0.0, 0.0, 1.0), 0.0, 1.0,
+        Projection::Orthographic { height: 0.0 }).is_none());
+}
+
+#[test]
+fn equirectangular_edges_look_back() {
+    let cam = projected(Projection::Equirectangular, 0.0, 2.0);
+    let dir = |x: f32, y: f32| cam.ray(x, y).unwrap().dir.norm();
+    assert!(close(dir(0.5, 0.5), v(1.0, 0.0, 0.0)));
+    assert!(close(dir(0.0, 0.5), v(-1.0, 0.0, 0.0)));
+    assert!(close(dir(1.0, 0.5), v(-1.0, 0.0, 0.0)));
+    // a quarter turn on each side, the poles at the top and bottom
+    assert!(close(dir(0.75, 0.5), v(0.0, -1.0, 0.0)));
+    assert!(close(dir(0.25, 0.5), v(0.0, 1.0, 0.0)));
+    assert!(close(dir(0.3, 0.0), v(0.0, 0.0, 1.0)));
+    assert!(close(dir(0.9, 1.0), v(0.0, 0.0, -1.0)));
+}
+
+#[test]
+fn fisheye_sees_nothing_past_its_circle() {
+    let full = projected(Projection::Fisheye, 360.0, 1.0);
+    assert!(close(full.ray(0.5, 0.5).unwrap().dir.norm(), v(1.0, 0.0, 0.0)));
+    assert!(close(full.ray(0.5, 0.25).unwrap().dir.norm(), v(0.0, 0.0, 1.0)));
+    // the rim looks straight back, the corners are out
+    assert!(close(full.ray(0.5, 0.0).unwrap().dir.norm(), v(-1.0, 0.0, 0.0)));
+    assert!(full.ray(0.0, 0.0).is_none() && full.ray(1.0, 0.9).is_none());
+    assert!(full.ray_through_lens(0.0, 0.0, 0.5, 0.5).is_none());
+
+    // a half sphere on a wide picture reaches back on its sides only
+    let wide = projected(Projection::Fisheye, 180.0, 2.0);
+    assert!(close(wide.ray(0.5, 0.0).unwrap().dir.norm(), v(0.0, 0.0, 1.0)));
+    assert!(close(wide.ray(1.0, 0.5).unwrap().dir.norm(), v(-1.0, 0.0, 0.0)));
+    assert!(wide.ray(1.0, 0.0).is_none());
+
+    // out of the circle the picture keeps the background
+    let SceneFile { scene, .. } = SceneFile::parse(include_str!("../scenes/default.toml")).unwrap();
+    let settings = Settings { width: 16, height: 16, threads: 1, ..Settings::default() };
+    let fb = render(&scene, &full, &settings);
+    assert_eq!(fb.get_pixel(0, 0), Some(settings.background));
+    assert!(fb.get_pixel(8, 8) != Some(settings.background));
+}
+
+#[test]
+fn lens_rays_of_a_pixel_meet_on_the_focus_plane() {
+    let eye = v(1.0, 2.0, 0.5);
+    let cam = Camera::look_at(eye, v(5.0, 2.0, 0.5), v(0.0, 0.0, 1.0), 70.0, 1.5).unwrap()
+        .with_lens(0.2, 3.0).unwrap();
+    let forward = v(1.0, 0.0, 0.0);
+    // where a ray crosses the plane `d` in front of the eye
+    let at = |r: &Ray<f32>, d: f32| {
+        let t = (d - (r.root - eye) * forward) / (r.dir * forward);
+        r.root + r.dir.scale(t)
+    };
+
+    for &(x, y) in [(0.5, 0.5), (0.1, 0.8), (0.95, 0.05)].iter() {
+        let pinhole = cam.ray(x, y).unwrap();
+        let focus = at(&pinhole, 3.0);
+        let mut spread: f32 = 0.0;
+        for i in 0..25 {
+            let (lu, lv) = ((i % 5) as f32 / 5.0 + 0.1, (i / 5) as f32 / 5.0 + 0.1);
+            let r = cam.ray_through_lens(x, y, lu, lv).unwrap();
+            // from the lens, in the plane of the eye
+            assert!((r.root - eye).len() <= 0.2 + 1e-5);
+            assert!(((r.root - eye) * forward).abs() < 1e-5);
+            assert!((at(&r, 3.0) - focus).len() < 1e-4);
+            spread = spread.max((at(&r, 1.5) - at(&pinhole, 1.5)).len());
+        }
+        // out of focus the rays are apart
+        assert!(spread > 0.05);
+    }
+
+    // without a lens the pinhole is kept
+    let sharp = cam.with_lens(0.0, 3.0).unwrap();
+    let (a, b) = (sharp.ray_through_lens(0.2, 0.3, 0.9, 0.1).unwrap(), sharp.ray(0.2, 0.3).unwrap());
+    assert!(close(a.root, b.root) && close(a.dir, b.dir));
+    assert!(cam.with_lens(-0.1, 3.0).is_none() && cam.with_lens(0.1, 0.0).is_none());
+}
@@ -0,0 +1,111 @@
+// the parser of the binary, built into this test on its own
+#[allow(dead_code)]
+#[path = "../src/cli.rs"]
+mod cli;
+
+use cli::{Command, Options};
+use raytracer::sampling::{Filter, Pattern};
+use raytracer::tonemap::{Operator, Transfer};
+use raytracer::PPM::ImageFormat;
+
+fn parse(args: &[&str]) -> Result<Options, String> {
+    match cli::parse(args.iter().map(|a| a.to_string()))? {
+        Command::Render(o) => Ok(o),
+        Command::Help => Err("help".to_string()),
+    }
+}
+
+fn err(args: &[&str]) -> String {
+    parse(args).err().expect("the arguments should be rejected")
+}
+
+#[test]
+fn values_follow_their_option_or_an_equal_sign() {
+    let a = parse(&["--width", "320", "--samples", "4", "--pattern", "halton", "--depth", "2", "room.toml"]).unwrap();
+    let b = parse(&["--width=320", "--samples=4", "--pattern=halton", "--depth=2", "room.toml"]).unwrap();
+    for o in [a, b].iter() {
+        assert_eq!((o.width, o.height, o.samples), (320, 800, 4));
+        assert_eq!(o.pattern, Pattern::Halton);
+        assert_eq!(o.depth, Some(2));
+        assert_eq!(o.scene, "room.toml");
+    }
+
+    let o = parse(&[]).unwrap();
+    assert_eq!(o.scene, "scenes/default.toml");
+    assert!(o.output.is_none() && !o.no_clobber && o.fov.is_none() && o.depth.is_none());
+    assert_eq!((o.pattern, o.filter), (Pattern::Grid, Filter::Box));
+    assert_eq!(parse(&["--filter", "Mitchell"]).unwrap().filter, Filter::Mitchell);
+    assert!(parse(&["--no-clobber"]).unwrap().no_clobber);
+    assert_eq!(parse(&["--fov", "75.5"]).unwrap().fov, Some(75.5));
+
+    let o = parse(&["--exposure=-1.5", "--tonemap", "reinhard", "--white", "4", "--transfer", "srgb"]).unwrap();
+    assert_eq!(o.tone_map.exposure, -1.5);
+    assert_eq!(o.tone_map.operator, Operator::Reinhard { white: 4.0 });
+    assert_eq!(o.tone_map.transfer, Transfer::Srgb);
+    assert!(matches!(cli::parse(["a.toml", "-h"].iter().map(|a| a.to_string())), Ok(Command::Help)));
+}
+
+#[test]
+fn bad_arguments_are_reported() {
+    assert_eq!(err(&["--width"]), "--width needs a value");
+    assert_eq!(err(&["-o"]), "-o needs a value");
+    assert_eq!(err(&["--width", "0"]), "--width: must be in 1..=16384, got 0");
+    assert_eq!(err(&["--height=20000"]), "--height: must be in 1..=16384, got 20000");
+    assert_eq!(err(&["--width", "-5"]), "--width: `-5` is not a whole number");
+    assert_eq!(err(&["--samples", "5000"]), "--samples: must be in 1..=4096, got 5000");
+    assert_eq!(err(&["--depth", "65"]), "--depth: must be in 0..=64, got 65");
+    assert_eq!(err(&["--fov", "400"]), "--fov: must be between 0 and 360 degrees, got 400");
+    assert_eq!(err(&["--fov", "wide"]), "--fov: `wide` is not a number");
+    assert_eq!(err(&["--exposure", "inf"]), "--exposure: `inf` is not a number");
+    assert_eq!(err(&["--tonemap", "hable"]), "--tonemap: unknown operator `hable`");
+    assert_eq!(err(&["--white", "2"]), "--white only makes sense with --tonemap reinhard");
+    assert_eq!(err(&["--tonemap=reinhard", "--white=0"]), "--white: must be a positive number, got `0`");
+    assert_eq!(err(&["--samples", "5"]),
+        "--samples: grid and jittered patterns need a square number (1, 4, 9, ...), got 5");
+    assert_eq!(parse(&["--samples", "5", "--pattern", "sobol"]).unwrap().samples, 5);
+    assert_eq!(err(&["--pattern", "poisson"]), "--pattern: unknown pattern `poisson`");
+    assert_eq!(err(&["--filter", "lanczos"]), "--filter: unknown filter `lanczos`");
+    assert_eq!(err(&["--size", "2"]), "unknown option `--size`");
+    assert_eq!(err(&["--no-clobber=yes"]), "unknown option `--no-clobber`");
+    assert_eq!(err(&["a.toml", "b.toml"]), "more than one scene file given");
+}
+
+#[test]
+fn adaptive_sampling_needs_more_samples() {
+    let o = parse(&["--samples", "16", "--adaptive", "0.05"]).unwrap();
+    assert_eq!(o.adaptive.map(|a| a.threshold), Some(0.05));
+    assert_eq!(err(&["--adaptive", "0.05"]), "--adaptive: needs --samples above the 4 rays of the first pass, got 1");
+    assert_eq!(err(&["--samples=4", "--adaptive=0.05"]),
+        "--adaptive: needs --samples above the 4 rays of the first pass, got 4");
+    for t in ["-0.1", "NaN", "2", "x"].iter() {
+        assert_eq!(err(&["--samples", "16", "--adaptive", t]),
+            format!("--adaptive: must be a number between 0 and 1, got `{}`", t));
+    }
+
+    // the binary stops before rendering anything
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_raytracer"))
+        .args(["--adaptive", "0.05", "-o", "-"].iter())
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(2));
+    assert!(out.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.starts_with("error: --adaptive: needs --samples above"), "{}", stderr);
+}
+
+#[test]
+fn the_format_follows_the_output() {
+    let format = |args: &[&str]| parse(args).unwrap().format;
+    assert_eq!(format(&[]), ImageFormat::Ppm);
+    assert_eq!(format(&["-o", "out.png"]), ImageFormat::Png);
+    assert_eq!(format(&["-o", "out.PPM"]), ImageFormat::Ppm);
+    assert_eq!(format(&["-o", "picture"]), ImageFormat::Ppm);
+    assert_eq!(format(&["-o", "out.png", "--format", "ppm"]), ImageFormat::Ppm);
+    assert_eq!(err(&["-o", "out.jpg"]), "cannot guess the format of `out.jpg`, use --format");
+    assert_eq!(err(&["--format", "jpg"]), "--format: unknown format `jpg`");
+
+    // stdout takes ppm unless told otherwise
+    let o = parse(&["-o", "-"]).unwrap();
+    assert_eq!((o.output.as_deref(), o.format), (Some("-"), ImageFormat::Ppm));
+    assert_eq!(format(&["-o", "-", "--format", "png"]), ImageFormat::Png);
+}
@@ -0,0 +1,24 @@
+// fixtures shared by the tests, each test file only uses some of them
+#![allow(dead_code)]
+
+use raytracer::III_vector::Vec3;
+use raytracer::objects::{SurfaceOptions, SurfaceType};
+use raytracer::PPM::RGB;
+
+pub fn v(x: f32, y: f32, z: f32) -> Vec3<f32> {
+    Vec3::new(x, y, z)
+}
+
+pub fn close(a: Vec3<f32>, b: Vec3<f32>) -> bool {
+    (a - b).len() < 1e-5
+}
+
+// gray, diffuse and lit by the ambient light too
+pub fn matte() -> SurfaceOptions {
+    SurfaceOptions::new(0.0, 1.0, 1.0, 1.0, SurfaceType::Solid(RGB::new(200, 200, 200)))
+}
+
+// white and diffuse only, all its light comes from the lights
+pub fn white() -> SurfaceOptions {
+    SurfaceOptions::new(0.0, 1.0, 0.0, 1.0, SurfaceType::Solid(RGB::new(255, 255, 255)))
+}
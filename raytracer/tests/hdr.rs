@@ -0,0 +1,57 @@
+use raytracer::hdr::{Framebuffer, RGBf};
+use raytracer::PPM::{ImageFormat, PPM, RGB};
+
+const HEADER: &[u8] = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n";
+
+fn hdr(fb: &Framebuffer) -> Vec<u8> {
+    let mut out = Vec::new();
+    fb.write_hdr_to(&mut out).unwrap();
+    out
+}
+
+#[test]
+fn header_gives_rows_then_columns() {
+    let fb = Framebuffer::new(3, 2);
+    let out = hdr(&fb);
+    let head = [HEADER, b"-Y 2 +X 3\n"].concat();
+    assert!(out.starts_with(&head));
+    // four bytes per pixel, black is all zero
+    assert_eq!(&out[head.len()..], &[0u8; 3 * 2 * 4][..]);
+}
+
+#[test]
+fn colors_share_one_exponent() {
+    // the pixel of a 1x1 picture
+    let pixel = |c: RGBf| {
+        let mut fb = Framebuffer::new(1, 1);
+        fb.set_pixel(0, 0, c);
+        let out = hdr(&fb);
+        [out[out.len() - 4], out[out.len() - 3], out[out.len() - 2], out[out.len() - 1]]
+    };
+    assert_eq!(pixel(RGBf::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+    assert_eq!(pixel(RGBf::new(1e-40, 0.0, 0.0)), [0, 0, 0, 0]);
+    assert_eq!(pixel(RGBf::new(1.0, 1.0, 1.0)), [128, 128, 128, 129]);
+    assert_eq!(pixel(RGBf::new(0.5, 0.25, 0.0)), [128, 64, 0, 128]);
+    assert_eq!(pixel(RGBf::new(3.0, 1.5, 0.75)), [192, 96, 48, 130]);
+    assert_eq!(pixel(RGBf::new(1000.0, 0.0, 2.0)), [250, 0, 0, 138]);
+    // negative channels cannot be stored
+    assert_eq!(pixel(RGBf::new(1.0, -1.0, 0.5)), [128, 0, 64, 129]);
+    assert_eq!(pixel(RGBf::new(f32::NAN, 0.0, 0.0)), [0, 0, 0, 0]);
+}
+
+#[test]
+fn rows_are_written_top_down() {
+    let mut fb = Framebuffer::new(2, 2);
+    fb.set_pixel(1, 0, RGBf::new(1.0, 0.0, 0.0));
+    fb.set_pixel(0, 1, RGBf::new(0.0, 0.0, 0.5));
+    let out = hdr(&fb);
+    let body = &out[out.len() - 16..];
+    assert_eq!(body, &[0, 0, 0, 0, 128, 0, 0, 129, 0, 0, 128, 128, 0, 0, 0, 0][..]);
+
+    // an 8 bit picture saved as hdr goes through the same path
+    let mut img = PPM::new(2, 2);
+    img.set_pixel(1, 0, RGB::new(255, 0, 0));
+    let mut out = Vec::new();
+    img.write_as(&mut out, ImageFormat::Hdr).unwrap();
+    assert_eq!(&out[out.len() - 12..out.len() - 8], &[128, 0, 0, 129]);
+}
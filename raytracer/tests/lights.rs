@@ -0,0 +1,207 @@
+use raytracer::hdr::RGBf;
+use raytracer::objects::{Falloff, Light, LightOptions, Plane, Scene, Sphere, SurfaceOptions, SurfaceType,
+    AREA_LIGHT_SAMPLES};
+use raytracer::scene_file::{SceneError, SceneFile};
+use raytracer::PPM::RGB;
+
+mod common;
+use common::{v, white};
+
+// white, diffuse only, of intensity 1
+fn opt() -> LightOptions {
+    LightOptions::new(0.0, 1.0, 0.0)
+}
+
+// a scene of a single light, with `extra` keys after the required ones
+fn light(extra: &str) -> Result<SceneFile, SceneError> {
+    SceneFile::parse(&format!("[[light]]\nposition = [0, 0, 1]\nspecular = 1\ndiffuse = 1\nambient = 0\n{}", extra))
+}
+
+fn light_err(extra: &str) -> String {
+    light(extra).err().unwrap().to_string()
+}
+
+#[test]
+fn area_lights_need_a_size() {
+    assert!(Light::sphere(v(0.0, 0.0, 0.0), 0.0, opt()).is_none());
+    assert!(Light::rect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0), v(2.0, 0.0, 0.0), opt()).is_none());
+    assert!(Light::disk(v(0.0, 0.0, 0.0), v(0.0, 0.0, 0.0), 1.0, opt()).is_none());
+    assert!(Light::disk(v(0.0, 0.0, 0.0), v(0.0, 0.0, 1.0), -1.0, opt()).is_none());
+
+    assert_eq!(Light::new(v(0.0, 0.0, 0.0), opt()).with_samples(8).samples(), 1);
+    let ball = Light::sphere(v(0.0, 0.0, 0.0), 1.0, opt()).unwrap();
+    assert_eq!(ball.samples(), AREA_LIGHT_SAMPLES);
+    assert_eq!(ball.with_samples(0).samples(), 1);
+}
+
+#[test]
+fn samples_spread_over_the_light() {
+    let center = v(0.0, 0.0, 4.0);
+    let p = v(0.0, 0.0, 0.0);
+
+    let rect = Light::rect(center, v(2.0, 0.0, 0.0), v(0.0, 1.0, 0.0), opt()).unwrap().with_samples(64);
+    let points = rect.sample_points(p, 1);
+    assert_eq!(points.len(), 64);
+    assert!(points.iter().all(|q| q.x.abs() <= 1.0 && q.y.abs() <= 0.5 && q.z == 4.0));
+    // both halves get some
+    assert!(points.iter().any(|q| q.x > 0.5) && points.iter().any(|q| q.x < -0.5));
+    assert_eq!(rect.sample_points(p, 1).iter().map(|q| q.x).sum::<f32>(), points.iter().map(|q| q.x).sum::<f32>());
+
+    let disk = Light::disk(center, v(1.0, 0.0, 0.0), 0.5, opt()).unwrap();
+    let points = disk.sample_points(p, 2);
+    assert_eq!(points.len(), AREA_LIGHT_SAMPLES as usize);
+    assert!(points.iter().all(|q| q.x.abs() < 1e-6 && (*q - center).len() <= 0.5 + 1e-6));
+
+    // a ball is seen as a disk facing the point
+    let ball = Light::sphere(center, 0.5, opt()).unwrap();
+    let points = ball.sample_points(p, 3);
+    assert!(points.iter().all(|q| (q.z - 4.0).abs() < 1e-6 && (*q - center).len() <= 0.5 + 1e-6));
+
+    let point = Light::new(center, opt());
+    assert_eq!(point.sample_points(p, 4).len(), 1);
+}
+
+#[test]
+fn area_lights_cast_penumbrae() {
+    // a ball above a floor, a wide panel higher up
+    let shade = |light: Light, x: f32| {
+        let mut scene = Scene::new();
+        scene.add_obj(Plane::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, 1.0), white()).unwrap());
+        scene.add_obj(Sphere::new(v(0.0, 0.0, 1.0), 0.5, white()).unwrap());
+        scene.add_light(light);
+        let eye = v(x, -3.0, 0.3);
+        scene.radiance_on_dir(eye, v(x, 0.0, 0.0) - eye).unwrap().r
+    };
+    let panel = Light::rect(v(0.0, 0.0, 4.0), v(2.0, 0.0, 0.0), v(0.0, 2.0, 0.0), opt()).unwrap().with_samples(64);
+    let point = Light::new(v(0.0, 0.0, 4.0), opt());
+
+    let (umbra, penumbra, open) = (shade(panel, 0.0), shade(panel, 0.9), shade(panel, 3.0));
+    assert_eq!(umbra, 0.0);
+    assert!(open > 0.0);
+    let unshadowed = shade(point, 0.9);
+    assert!(penumbra > 0.0 && penumbra < unshadowed);
+    // a point light leaves no half shadow there
+    assert!(shade(point, 0.0) == 0.0 && unshadowed > 0.0);
+}
+
+#[test]
+fn lights_are_read_from_scene_files() {
+    assert!(light("").is_ok());
+    assert!(light("shape = \"sphere\"\nradius = 0.5\nsamples = 4\n").is_ok());
+    assert!(light("shape = \"rect\"\nu = [1, 0, 0]\nv = [0, 1, 0]\n").is_ok());
+    assert!(light("shape = \"disk\"\nnormal = [0, 0, -1]\nradius = 0.5\n").is_ok());
+
+    assert_eq!(light_err("shape = \"rect\"\nu = [1, 0, 0]\nv = [1, 0, 0]\n"),
+        "line 6: light edges `u` and `v` must not be zero or parallel");
+    assert_eq!(light_err("shape = \"disk\"\nnormal = [0, 0, 0]\nradius = 1\n"),
+        "line 6: light `normal` must not be zero");
+    assert_eq!(light_err("shape = \"sphere\"\n"), "line 1: missing `radius` in [light]");
+    assert!(light_err("shape = \"tube\"\n").starts_with("line 6: unknown light shape \"tube\""));
+    assert_eq!(light_err("shape = \"sphere\"\nradius = 1\nsamples = 2.5\n"),
+        "line 8: `samples` must be a whole number above 0, got 2.5");
+    // a point light has a single ray to cast
+    assert_eq!(light_err("samples = 4\n"), "line 6: `samples` is only for area lights, a point light casts one ray");
+    assert_eq!(light_err("shape = \"point\"\nsamples = 4\n"),
+        "line 7: `samples` is only for area lights, a point light casts one ray");
+}
+
+// the floor right below a point light at height h, seen from the side
+fn floor_under(light: LightOptions, h: f32, surface: RGB) -> RGBf {
+    let mut scene = Scene::new();
+    let floor = SurfaceOptions::new(0.0, 1.0, 0.0, 1.0, SurfaceType::Solid(surface));
+    scene.add_obj(Plane::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, 1.0), floor).unwrap());
+    scene.add_light(Light::new(v(0.0, 0.0, h), light));
+    let eye = v(-1.0, 0.0, 1.0);
+    scene.radiance_on_dir(eye, v(0.0, 0.0, 0.0) - eye).unwrap()
+}
+
+#[test]
+fn lights_have_a_color() {
+    let white = RGB::new(255, 255, 255);
+    let plain = floor_under(opt(), 1.0, white);
+    let default = opt().with_color(RGBf::new(1.0, 1.0, 1.0)).with_intensity(1.0).with_falloff(Falloff::InverseSquare);
+    assert_eq!(floor_under(default, 1.0, white), plain);
+    assert!(plain.r > 0.0 && plain.r == plain.g && plain.g == plain.b);
+
+    let red = floor_under(opt().with_color(RGBf::new(1.0, 0.0, 0.0)), 1.0, white);
+    assert_eq!(red, RGBf::new(plain.r, 0.0, 0.0));
+    // channel by channel : a red light on a blue floor leaves it black
+    let blue = floor_under(opt().with_color(RGBf::new(1.0, 0.0, 0.0)), 1.0, RGB::new(0, 0, 255));
+    assert_eq!(blue, RGBf::new(0.0, 0.0, 0.0));
+
+    let twice = floor_under(opt().with_intensity(2.0), 1.0, white);
+    assert!((twice.g - 2.0 * plain.g).abs() < 1e-6);
+}
+
+#[test]
+fn lights_fade_with_the_distance() {
+    let white = RGB::new(255, 255, 255);
+    let near = |f: Falloff| floor_under(opt().with_falloff(f), 1.0, white).r;
+    let far = |f: Falloff| floor_under(opt().with_falloff(f), 2.0, white).r;
+
+    assert_eq!(near(Falloff::None), far(Falloff::None));
+    assert!((near(Falloff::InverseSquare) / far(Falloff::InverseSquare) - 4.0).abs() < 1e-4);
+    // one unit away the inverse square changes nothing
+    assert!((near(Falloff::InverseSquare) - near(Falloff::None)).abs() < 1e-4);
+
+    let linear = Falloff::attenuation(0.0, 1.0, 0.0).unwrap();
+    assert!((near(linear) / far(linear) - 2.0).abs() < 1e-4);
+    assert_eq!(Falloff::attenuation(1.0, 0.0, 1.0).unwrap().factor(1.0), 0.5);
+    assert!(Falloff::attenuation(0.0, 0.0, 0.0).is_none());
+    assert!(Falloff::attenuation(1.0, -1.0, 0.0).is_none());
+    assert!(Falloff::attenuation(f32::NAN, 1.0, 0.0).is_none());
+}
+
+#[test]
+fn intensity_is_measured_in_the_units_of_the_picture() {
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+    let color = RGB::new(128, 64, 255);
+    // head on, one unit away, a light of intensity 1 shows the surface color
+    let c = floor_under(opt(), 1.0, color);
+    assert!(close(c.r, 128.0 / 255.0) && close(c.g, 64.0 / 255.0) && close(c.b, 1.0), "{:?}", c);
+
+    assert!(close(floor_under(opt().with_intensity(9.0), 3.0, color).b, 1.0));
+    assert!(close(floor_under(opt().with_intensity(0.5), 0.5, color).b, 2.0));
+    assert!(close(floor_under(opt().with_falloff(Falloff::None), 5.0, color).b, 1.0));
+    // the weights take their share of the intensity
+    assert!(close(floor_under(LightOptions::new(0.0, 0.5, 0.0), 1.0, color).b, 0.5));
+
+    // the ambient term is light in itself, the intensity and distance do
+    // not change it
+    let ambient = |l: LightOptions| {
+        let mut scene = Scene::new();
+        let lit_by_ambient = SurfaceOptions::new(0.0, 0.0, 1.0, 1.0, SurfaceType::Solid(color));
+        scene.add_obj(Plane::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, 1.0), lit_by_ambient).unwrap());
+        scene.add_light(Light::new(v(0.0, 0.0, 10.0), l));
+        scene.radiance_on_dir(v(-1.0, 0.0, 1.0), v(1.0, 0.0, -1.0)).unwrap()
+    };
+    let dim = LightOptions::new(0.0, 0.0, 0.02);
+    assert!(close(ambient(dim).b, 0.02) && close(ambient(dim).r, 0.02 * 128.0 / 255.0));
+    assert_eq!(ambient(dim.with_intensity(100.0)), ambient(dim));
+}
+
+#[test]
+fn light_colors_are_read_from_scene_files() {
+    assert!(light("color = [255, 128, 0]\nintensity = 3.5\nfalloff = \"inverse_square\"\n").is_ok());
+    assert!(light("falloff = \"none\"\n").is_ok());
+    assert!(light("attenuation = [1, 0.5, 0.25]\n").is_ok());
+
+    assert!(light_err("falloff = \"linear\"\n").starts_with("line 6: unknown falloff \"linear\""));
+    assert_eq!(light_err("attenuation = [0, 0, 0]\n"),
+        "line 6: `attenuation` terms must not be negative nor all zero");
+    assert_eq!(light_err("falloff = \"none\"\nattenuation = [1, 0, 0]\n"),
+        "line 6: a light takes either `falloff` or `attenuation`, not both");
+    assert!(light_err("color = [300, 0, 0]\n").starts_with("line 6: `color` components"));
+    assert!(light_err("intensity = -1\n").starts_with("line 6:"));
+
+    // a position is enough : intensity 1, inverse square, plain weights and
+    // no ambient light
+    let floor = "[[plane]]\npoint = [0, 0, 0]\nnormal = [0, 0, 1]\nsurface = \"solid\"\ncolor = [255, 255, 255]\n\
+        specular = 0\nshininess = 1\n";
+    let seen = |light: &str| {
+        let scene = SceneFile::parse(&format!("{}[[light]]\n{}", floor, light)).unwrap().scene;
+        scene.radiance_on_dir(v(-1.0, 0.0, 1.0), v(1.0, 0.0, -1.0)).unwrap().g
+    };
+    assert!((seen("position = [0, 0, 2]\n") - 0.25).abs() < 1e-5);
+    assert!((seen("position = [0, 0, 2]\nintensity = 4\ndiffuse = 0.5\n") - 0.5).abs() < 1e-5);
+}
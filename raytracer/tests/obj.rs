@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::Path;
+
+use raytracer::III_vector::Vec3;
+use raytracer::obj::{load_obj, parse_obj, Material};
+use raytracer::objects::{Shape, SurfaceType};
+use raytracer::scene_file::SceneFile;
+
+#[test]
+fn polygons_are_fanned_and_split_by_group() {
+    let text = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+g floor
+f 1/1 2/2 3/3 4/4
+g wall
+f -5 -4 -1   # relative indices
+";
+    let parts = parse_obj(text, Path::new("shapes.obj")).unwrap();
+    assert_eq!(parts.len(), 2);
+    assert_eq!((parts[0].group.as_str(), parts[0].mesh.len()), ("floor", 2));
+    assert_eq!(parts[0].mesh.uvs(), &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)][..]);
+    assert_eq!((parts[1].group.as_str(), parts[1].mesh.len()), ("wall", 1));
+    assert!(parts[1].mesh.uvs().is_empty());
+    assert!(parts[0].material.is_none());
+
+    // the quad covers the unit square
+    let d = parts[0].mesh.ray_intersect(Vec3::new(0.9, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    assert_eq!(d, Some(1.0));
+}
+
+#[test]
+fn malformed_lines_are_reported_with_file_and_line() {
+    let cases = [
+        ("v 1 2\n", "bad.obj:1: vertex: expected 3 to 4 numbers, got 2"),
+        ("v 0 0 0\nv 1 0 0\n\nf 1 2\n", "bad.obj:4: a face needs at least 3 vertices, got 2"),
+        ("v 0 0 0\nf 1 2 3\n", "bad.obj:2: vertex index 2 out of range, there are 1"),
+        ("v 0 0 0\nf 1 1/x 1\n", "bad.obj:2: bad texture index `x` in `1/x`"),
+        ("vn 0 0 0\n", "bad.obj:1: normal: must not be zero"),
+        ("vn 1e-30 0 0\n", "bad.obj:1: normal: must not be zero"),
+        ("vn 1e30 1e30 0\n", "bad.obj:1: normal: must not be zero"),
+        ("usemtl red\n", "bad.obj:1: unknown material `red`"),
+    ];
+    for (text, msg) in cases.iter() {
+        let err = parse_obj(text, Path::new("bad.obj")).err().expect(text);
+        assert_eq!(err.to_string(), *msg);
+    }
+}
+
+#[test]
+fn materials_and_models_load_from_files() {
+    let dir = std::env::temp_dir().join(format!("raytracer-obj-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("m.mtl"), "newmtl red\nKd 1 0 0\nKs 0.5\nNs 20\n\nnewmtl bad\nNi -1\n").unwrap();
+    fs::write(dir.join("ok.mtl"), "newmtl red\nKd 1 0 0\nKs 0.5 0.5 0.5\nNs 20\nnewmtl glass\nNi 1.5\nd 0.1\n").unwrap();
+    fs::write(dir.join("tri.obj"), "mtllib ok.mtl\nv 1 0 0\nv 1 1 0\nv 1 0 1\nusemtl red\nf 1 2 3\n\
+        usemtl glass\nf 3 2 1\n").unwrap();
+    fs::write(dir.join("broken.obj"), "mtllib m.mtl\n").unwrap();
+    fs::write(dir.join("scene.toml"), "[[mesh]]\nfile = \"tri.obj\"\n").unwrap();
+
+    let parts = load_obj(dir.join("tri.obj")).unwrap();
+    let names: Vec<_> = parts.iter().map(|p| p.material.clone().unwrap()).collect();
+    assert_eq!(names, ["red", "glass"]);
+    // Ni 1.5 slows the light down : the sines shrink by 1 / 1.5 on the way in
+    let glass = Material { ni: 1.5, d: 0.1, ..Material::default() };
+    match glass.surface().surface_type() {
+        SurfaceType::Transparent(k) => assert!((k - 1.0 / 1.5).abs() < 1e-6),
+        _ => panic!("glass should be transparent"),
+    }
+
+    let err = load_obj(dir.join("broken.obj")).err().unwrap().to_string();
+    assert!(err.ends_with("m.mtl:7: Ni: must be positive, got -1"), "{}", err);
+
+    // scenes find their models next to them
+    SceneFile::load(dir.join("scene.toml")).unwrap();
+    let err = SceneFile::parse("[[mesh]]\nfile = \"missing.obj\"\n").err().unwrap().to_string();
+    assert!(err.starts_with("line 2: missing.obj: "), "{}", err);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
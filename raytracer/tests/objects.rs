@@ -0,0 +1,209 @@
+use raytracer::III_vector::Vec3;
+use raytracer::objects::{Light, LightOptions, Plane, Quad, Scene, Shape, Sphere, Triangle, TriangleMesh, SurfaceOptions,
+    SurfaceType};
+use raytracer::scene_file::SceneFile;
+
+mod common;
+use common::{matte, v};
+
+#[test]
+fn plane_is_hit_from_both_sides_but_not_along_it() {
+    let floor = Plane::new(v(0.0, 0.0, -1.0), v(0.0, 0.0, 2.0), matte()).unwrap();
+    let d = floor.ray_intersect(v(0.0, 0.0, 1.0), v(1.0, 0.0, -1.0)).unwrap();
+    assert!((d - 2.0 * 2f32.sqrt()).abs() < 1e-5);
+    assert!(floor.ray_intersect(v(0.0, 0.0, -3.0), v(0.0, 0.0, 1.0)).is_some());
+    assert!(floor.ray_intersect(v(0.0, 0.0, 1.0), v(1.0, 0.0, 0.0)).is_none());
+    assert!(floor.ray_intersect(v(0.0, 0.0, 1.0), v(0.0, 0.0, 1.0)).is_none());
+
+    // the normal faces the ray whatever side it comes from
+    let hit = floor.intersect(v(0.0, 0.0, -3.0), v(0.0, 0.0, 1.0)).unwrap();
+    assert_eq!((hit.normal.x, hit.normal.y, hit.normal.z), (0.0, 0.0, -1.0));
+    assert!(!hit.front_face);
+    assert!(Plane::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, 0.0), matte()).is_none());
+}
+
+#[test]
+fn quad_is_only_hit_inside_its_edges() {
+    let wall = Quad::new(v(2.0, -1.0, 0.0), v(0.0, 2.0, 0.0), v(0.0, 0.0, 1.0), matte()).unwrap();
+    assert_eq!(wall.ray_intersect(v(0.0, 0.0, 0.5), v(1.0, 0.0, 0.0)), Some(2.0));
+    assert!(wall.ray_intersect(v(0.0, 0.0, 1.5), v(1.0, 0.0, 0.0)).is_none());
+    assert!(wall.ray_intersect(v(0.0, 1.5, 0.5), v(1.0, 0.0, 0.0)).is_none());
+    assert!(Quad::new(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0), v(2.0, 0.0, 0.0), matte()).is_none());
+}
+
+#[test]
+fn triangle_hits_inside_and_on_its_edges() {
+    let t = Triangle::new(v(1.0, 0.0, 0.0), v(1.0, 1.0, 0.0), v(1.0, 0.0, 1.0), matte()).unwrap();
+    assert_eq!(t.ray_intersect(v(0.0, 0.25, 0.25), v(2.0, 0.0, 0.0)), Some(1.0));
+    assert_eq!(t.ray_intersect(v(0.0, 0.5, 0.5), v(1.0, 0.0, 0.0)), Some(1.0));
+    assert!(t.ray_intersect(v(0.0, 0.6, 0.6), v(1.0, 0.0, 0.0)).is_none());
+    assert!(t.ray_intersect(v(2.0, 0.25, 0.25), v(1.0, 0.0, 0.0)).is_none());
+    assert!(Triangle::new(v(0.0, 0.0, 0.0), v(1.0, 1.0, 1.0), v(2.0, 2.0, 2.0), matte()).is_none());
+}
+
+#[test]
+fn mesh_blends_vertex_normals() {
+    let square = vec![v(1.0, -1.0, -1.0), v(1.0, 1.0, -1.0), v(1.0, -1.0, 1.0), v(1.0, 1.0, 1.0)];
+    let faces = vec![[0, 1, 2], [2, 1, 3]];
+    // the normals lean toward -y on one side and +y on the other
+    let normals = vec![v(-1.0, -1.0, 0.0), v(-1.0, 1.0, 0.0), v(-1.0, -1.0, 0.0), v(-1.0, 1.0, 0.0)];
+    let mesh = TriangleMesh::new(square.clone(), faces.clone(), Some(normals), matte()).unwrap();
+
+    let hit = mesh.intersect(v(0.0, 0.0, 0.5), v(1.0, 0.0, 0.0)).unwrap();
+    let n = hit.normal;
+    assert!((hit.t - 1.0).abs() < 1e-6);
+    assert!((n.x + 1.0).abs() < 1e-5 && n.y.abs() < 1e-5 && n.z.abs() < 1e-5);
+    let n = mesh.intersect(v(0.0, 0.5, 0.0), v(1.0, 0.0, 0.0)).unwrap().normal;
+    assert!(n.y > 0.0 && n.x < 0.0);
+
+    // without normals a flat mesh shades flat
+    let flat = TriangleMesh::new(square.clone(), faces.clone(), None, matte()).unwrap();
+    let n = flat.intersect(v(0.0, 0.5, 0.0), v(1.0, 0.0, 0.0)).unwrap().normal;
+    assert!((n.x + 1.0).abs() < 1e-5);
+
+    assert!(TriangleMesh::new(square.clone(), vec![[0, 1, 4]], None, matte()).is_none());
+    assert!(TriangleMesh::new(square, faces, Some(vec![v(1.0, 0.0, 0.0)]), matte()).is_none());
+}
+
+#[test]
+fn hit_records_carry_point_uv_and_material() {
+    let glass = SurfaceOptions::new(1.0, 1.0, 0.0, 10.0, SurfaceType::Transparent(1.3));
+    let ball = Sphere::new(v(2.0, 0.0, 0.0), 1.0, glass).unwrap();
+    let hit = ball.intersect(v(0.0, 0.0, 0.0), v(3.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - 1.0).abs() < 1e-6 && (hit.point.x - 1.0).abs() < 1e-6);
+    assert!((hit.normal.x + 1.0).abs() < 1e-6 && hit.front_face);
+    assert!((hit.uv.0 - 1.0).abs() < 1e-6 && (hit.uv.1 - 0.5).abs() < 1e-6);
+    assert!(matches!(hit.material.surface_type(), SurfaceType::Transparent(k) if k == 1.3));
+
+    // light crosses a glass sheet straight
+    let sheet = Quad::new(v(1.0, -1.0, -1.0), v(0.0, 2.0, 0.0), v(0.0, 0.0, 2.0), glass).unwrap();
+    let hit = sheet.intersect(v(0.0, 0.5, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert_eq!(hit.uv, (0.75, 0.5));
+    let out = sheet.transmit(&hit, v(1.0, 0.0, 0.0)).unwrap();
+    assert_eq!((out.dir.x, out.dir.y, out.dir.z), (1.0, 0.0, 0.0));
+
+    let wall = Quad::new(v(1.0, -1.0, -1.0), v(0.0, 2.0, 0.0), v(0.0, 0.0, 2.0), matte()).unwrap();
+    let hit = wall.intersect(v(0.0, 0.5, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert!(wall.transmit(&hit, v(1.0, 0.0, 0.0)).is_none());
+}
+
+#[test]
+fn spheres_are_hit_from_any_origin() {
+    let ball = Sphere::new(v(0.0, 0.0, 0.0), 2.0, matte()).unwrap();
+    assert_eq!(ball.roots(v(-5.0, 0.0, 0.0), v(2.0, 0.0, 0.0)), Some((3.0, 7.0)));
+    assert_eq!(ball.roots(v(-5.0, 3.0, 0.0), v(1.0, 0.0, 0.0)), None);
+
+    // from inside the far wall, its normal turned inwards
+    let hit = ball.intersect(v(0.5, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - 1.5).abs() < 1e-6);
+    assert!((hit.normal.x + 1.0).abs() < 1e-6 && !hit.front_face);
+    assert_eq!(ball.ray_intersect(v(0.0, 0.0, 0.0), v(0.0, 0.0, -1.0)), Some(2.0));
+
+    // a big sphere mostly behind the origin is still met in front
+    let big = Sphere::new(v(-9.0, 0.0, 0.0), 10.0, matte()).unwrap();
+    assert!((big.ray_intersect(v(0.5, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap() - 0.5).abs() < 1e-5);
+    assert!(big.ray_intersect(v(2.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).is_none());
+
+    // a ray leaving the surface does not meet it again unless it goes through
+    assert_eq!(ball.ray_intersect(v(2.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), None);
+    assert_eq!(ball.ray_intersect(v(2.0, 0.0, 0.0), v(-1.0, 0.0, 0.0)), Some(4.0));
+    assert_eq!(ball.hit_after(v(-5.0, 0.0, 0.0), v(1.0, 0.0, 0.0), 4.0), Some(7.0));
+    let loose = ball.with_t_min(0.5);
+    assert_eq!(loose.ray_intersect(v(-2.2, 0.0, 0.0), v(1.0, 0.0, 0.0)), Some(4.2));
+}
+
+#[test]
+fn glass_spheres_bend_rays_in_and_out() {
+    let glass = SurfaceOptions::new(1.0, 1.0, 0.0, 10.0, SurfaceType::Transparent(1.3));
+    let ball = Sphere::new(v(2.0, 0.0, 0.0), 1.0, glass).unwrap();
+
+    // straight through the center
+    let hit = ball.intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    let out = ball.transmit(&hit, v(1.0, 0.0, 0.0)).unwrap();
+    assert!((out.root.x - 3.0).abs() < 1e-5 && out.root.y.abs() < 1e-5);
+    assert!((out.dir.x - 1.0).abs() < 1e-5 && out.dir.y.abs() < 1e-5);
+
+    // off center the ray leaves on the far side, bent the same way twice
+    let hit = ball.intersect(v(0.0, 0.3, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    let out = ball.transmit(&hit, v(1.0, 0.0, 0.0)).unwrap();
+    assert!(((out.root - v(2.0, 0.0, 0.0)).len() - 1.0).abs() < 1e-5);
+    assert!(out.root.x > 2.0 && out.dir.y > 0.0);
+    assert!((out.dir.len() - 1.0).abs() < 1e-5);
+
+    // from inside it is only bent out
+    let hit = ball.intersect(v(2.0, 0.0, 0.0), v(1.0, 1.0, 0.0)).unwrap();
+    let out = ball.transmit(&hit, v(1.0, 1.0, 0.0)).unwrap();
+    assert!((out.root - hit.point).len() < 1e-6);
+    assert!((out.dir - v(1.0, 1.0, 0.0).norm()).len() < 1e-5);
+}
+
+#[test]
+fn grazing_rays_pass_glass_spheres() {
+    let glass = SurfaceOptions::new(1.0, 1.0, 0.0, 10.0, SurfaceType::Transparent(1.1));
+    let center = v(-2.18, -4.19, 4.25);
+    let mut scene = Scene::new();
+    scene.add_obj(Sphere::new(center, 0.01, glass).unwrap());
+    scene.add_light(Light::new(v(0.0, 0.0, 5.0), LightOptions::new(0.0, 100.0, 1.0)));
+    assert!(scene.radiance_on_dir(v(0.0, 0.0, 0.0), v(-2.185, -4.186, 4.247)).is_some());
+    assert!(scene.radiance_on_dir(v(0.0, 0.0, 0.0), v(-2.1803396, -4.1963844, 4.2435317)).is_some());
+
+    // the refracted ray runs along the surface when the sine of the incoming
+    // angle is close to 1 / 1.1
+    let ball = Sphere::new(center, 0.01, glass).unwrap();
+    let side = Vec3::prod(center.norm(), v(0.0, 0.0, 1.0)).norm();
+    let up = Vec3::prod(side, center.norm());
+    for i in 0..2000 {
+        let a = i as f32 * 0.0031;
+        let r = 0.01 * (0.9085 + (i % 40) as f32 * 5e-5);
+        let dir = center + side.scale(r * a.cos()) + up.scale(r * a.sin());
+        if let Some(hit) = ball.intersect(v(0.0, 0.0, 0.0), dir) {
+            let out = ball.transmit(&hit, dir).unwrap();
+            assert!((out.root - center).len() < 0.01 + 1e-4);
+            assert!((out.dir.len() - 1.0).abs() < 1e-4);
+        }
+    }
+}
+
+#[test]
+fn camera_inside_a_dome_sees_it() {
+    let mut scene = Scene::new();
+    scene.add_obj(Sphere::new(v(0.0, 0.0, 0.0), 10.0, matte()).unwrap());
+    scene.add_light(Light::new(v(0.0, 0.0, 5.0), LightOptions::new(0.0, 100.0, 1.0)));
+    assert_eq!(scene.ind_viewing(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), Some(0));
+    let lit = scene.radiance_on_dir(v(0.0, 0.0, 0.0), v(0.0, 0.0, -1.0)).unwrap();
+    assert!(lit.r > 0.0);
+}
+
+#[test]
+fn plane_and_quad_tables_are_parsed() {
+    let surface = "surface = \"mirror\"\nspecular = 1\nshininess = 1\n";
+    let scene = SceneFile::parse(&format!("[[plane]]\npoint = [0, 0, -1]\nnormal = [0, 0, 3]\n{}\
+        [[quad]]\ncorner = [2, -1, 0]\nu = [0, 2, 0]\nv = [0, 0, 1]\n{}", surface, surface)).unwrap().scene;
+    assert_eq!(scene.ind_viewing(v(0.0, 0.0, 0.0), v(0.0, 0.0, -1.0)), Some(0));
+    assert_eq!(scene.ind_viewing(v(0.0, 0.0, 0.5), v(1.0, 0.0, 0.0)), Some(1));
+    assert_eq!(scene.ind_viewing(v(0.0, 0.0, 0.5), v(0.0, 0.0, 1.0)), None);
+
+    let err = SceneFile::parse(&format!("[[plane]]\npoint = [0, 0, 0]\nnormal = [0, 0, 0]\n{}", surface)).err().unwrap();
+    assert_eq!(err.to_string(), "line 1: plane `normal` must not be zero");
+    let err = SceneFile::parse(&format!("[[quad]]\ncorner = [0, 0, 0]\nu = [1, 0, 0]\nv = [2, 0, 0]\n{}", surface))
+        .err().unwrap();
+    assert_eq!(err.to_string(), "line 1: quad edges `u` and `v` must not be zero or parallel");
+}
+
+#[test]
+fn mesh_tables_are_checked() {
+    let surface = "surface = \"mirror\"\nspecular = 1\nshininess = 1\n";
+    let scene = SceneFile::parse(&format!("[[mesh]]\nvertices = [[1, -1, -1], [1, 1, -1],\n\
+        [1, 0, 1]]\nfaces = [[0, 1, 2]]\n{}", surface)).unwrap().scene;
+    assert_eq!(scene.ind_viewing(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), Some(0));
+
+    let err = SceneFile::parse(&format!("[[mesh]]\nvertices = [[1, -1, -1], [1, 1, -1], [1, 0, 1]]\n\
+        faces = [[0, 1, 3]]\n{}", surface)).err().unwrap();
+    assert_eq!(err.to_string(), "line 3: face [0.0, 1.0, 3.0] does not index one of the 3 vertices");
+    let err = SceneFile::parse(&format!("[[mesh]]\nvertices = [[1, -1], [1, 1, -1]]\nfaces = [[0, 1, 1]]\n{}",
+        surface)).err().unwrap();
+    assert_eq!(err.to_string(), "line 2: `vertices` must be an array of arrays of 3 numbers");
+    let err = SceneFile::parse(&format!("[[triangle]]\na = [0, 0, 0]\nb = [1, 1, 1]\nc = [2, 2, 2]\n{}", surface))
+        .err().unwrap();
+    assert_eq!(err.to_string(), "line 1: triangle corners must not be on a line");
+}
@@ -0,0 +1,215 @@
+use raytracer::png::{adler32, crc32};
+use raytracer::PPM::{PPM, RGB};
+
+#[test]
+fn checksums_match_known_values() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    // the CRC every PNG ends with
+    assert_eq!(crc32(b"IEND"), 0xae42_6082);
+
+    assert_eq!(adler32(b""), 1);
+    assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    // long enough for the sums to be reduced on the way
+    assert_eq!(adler32(&[0xff; 100_000]), 0x149a_302c);
+}
+
+// reads the bits of a deflate stream, least significant first
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Bits<'a> {
+    fn bit(&mut self) -> u32 {
+        let b = (self.data[self.pos / 8] >> (self.pos % 8)) & 1;
+        self.pos += 1;
+        b as u32
+    }
+
+    fn get(&mut self, count: u32) -> u32 {
+        (0..count).fold(0, |acc, i| acc | self.bit() << i)
+    }
+
+    // the literal / length alphabet of the fixed codes
+    fn symbol(&mut self) -> u32 {
+        let mut code = 0;
+        for len in 1..=9 {
+            code = code << 1 | self.bit();
+            match (len, code) {
+                (7, 0..=23) => return 256 + code,
+                (8, 0x30..=0xbf) => return code - 0x30,
+                (8, 0xc0..=0xc7) => return 280 + code - 0xc0,
+                (9, 0x190..=0x1ff) => return 144 + code - 0x190,
+                _ => {}
+            }
+        }
+        panic!("bad fixed code at bit {}", self.pos);
+    }
+}
+
+const LEN_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LEN_EXTRA: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u32; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+// stored and fixed code blocks, the ones the encoder may write
+fn inflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = Bits { data, pos: 0 };
+    let mut out: Vec<u8> = Vec::new();
+    loop {
+        let last = bits.bit();
+        match bits.get(2) {
+            0 => {
+                bits.pos = bits.pos.div_ceil(8) * 8;
+                let len = bits.get(16) as usize;
+                assert_eq!(bits.get(16) as usize, !len & 0xffff);
+                let start = bits.pos / 8;
+                out.extend_from_slice(&data[start..start + len]);
+                bits.pos += 8 * len;
+            }
+            1 => loop {
+                let sym = bits.symbol();
+                match sym {
+                    0..=255 => out.push(sym as u8),
+                    256 => break,
+                    _ => {
+                        let l = (sym - 257) as usize;
+                        let len = LEN_BASE[l] + bits.get(LEN_EXTRA[l]) as usize;
+                        // distance codes are 5 bits, most significant first
+                        let d = (0..5).fold(0, |acc, _| acc << 1 | bits.bit()) as usize;
+                        let dist = DIST_BASE[d] + bits.get(DIST_EXTRA[d]) as usize;
+                        assert!(dist <= out.len() && dist <= 1 << 15);
+                        for _ in 0..len {
+                            out.push(out[out.len() - dist]);
+                        }
+                    }
+                }
+            },
+            kind => panic!("unexpected block type {}", kind),
+        }
+        if last == 1 {
+            return out;
+        }
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+}
+
+fn be32(b: &[u8]) -> u32 {
+    b[..4].iter().fold(0, |acc, &x| acc << 8 | x as u32)
+}
+
+// the RGB bytes of a PNG written by the encoder, and its size
+fn decode(png: &[u8]) -> (u32, u32, Vec<u8>) {
+    assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']);
+    let mut pos = 8;
+    let (mut width, mut height) = (0, 0);
+    let mut stream = Vec::new();
+    let mut kinds = Vec::new();
+    while pos < png.len() {
+        let len = be32(&png[pos..pos + 4]) as usize;
+        let kind = &png[pos + 4..pos + 8];
+        let data = &png[pos + 8..pos + 8 + len];
+        let crc = be32(&png[pos + 8 + len..pos + 12 + len]);
+        assert_eq!(crc, crc32(&png[pos + 4..pos + 8 + len]));
+        match kind {
+            b"IHDR" => {
+                width = be32(&data[0..4]);
+                height = be32(&data[4..8]);
+                assert_eq!(&data[8..], &[8, 2, 0, 0, 0]);
+            }
+            b"IDAT" => stream.extend_from_slice(data),
+            _ => {}
+        }
+        kinds.push(String::from_utf8(kind.to_vec()).unwrap());
+        pos += 12 + len;
+    }
+    assert_eq!(kinds.first().map(|k| k.as_str()), Some("IHDR"));
+    assert_eq!(kinds.last().map(|k| k.as_str()), Some("IEND"));
+    // IDAT chunks hold at most 64K each
+    assert!(stream.len() <= (1 << 16) * kinds.iter().filter(|k| *k == "IDAT").count());
+
+    // zlib : deflate with a 32K window, no preset dictionary
+    assert_eq!(stream[0] & 0x0f, 8);
+    assert_eq!((stream[0] as u16 * 256 + stream[1] as u16) % 31, 0);
+    let filtered = inflate(&stream[2..stream.len() - 4]);
+    let adler = be32(&stream[stream.len() - 4..]);
+    assert_eq!(adler, adler32(&filtered));
+
+    let stride = 3 * width as usize;
+    assert_eq!(filtered.len(), (stride + 1) * height as usize);
+    let mut rgb: Vec<u8> = Vec::with_capacity(stride * height as usize);
+    for (y, line) in filtered.chunks(stride + 1).enumerate() {
+        for i in 0..stride {
+            let a = if i >= 3 { rgb[y * stride + i - 3] } else { 0 };
+            let b = if y > 0 { rgb[(y - 1) * stride + i] } else { 0 };
+            let c = if i >= 3 && y > 0 { rgb[(y - 1) * stride + i - 3] } else { 0 };
+            let pred = match line[0] {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                f => panic!("unknown filter {}", f),
+            };
+            rgb.push(line[1 + i].wrapping_add(pred));
+        }
+    }
+    (width, height, rgb)
+}
+
+fn round_trip(img: &PPM) {
+    let mut out = Vec::new();
+    img.write_png_to(&mut out).unwrap();
+    let (w, h, rgb) = decode(&out);
+    assert_eq!((w, h), (img.width(), img.height()));
+    for y in 0..h {
+        for x in 0..w {
+            let i = 3 * (y * w + x) as usize;
+            let p = img.get_pixel(x, y).unwrap();
+            assert_eq!((rgb[i], rgb[i + 1], rgb[i + 2]), (p.r, p.g, p.b), "pixel ({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn pictures_decode_back_to_their_pixels() {
+    // smooth, flat and noisy rows get different filters
+    let mut img = PPM::new(37, 11);
+    let mut seed = 7u32;
+    for y in 0..11 {
+        for x in 0..37 {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let c = match y % 3 {
+                0 => RGB::new(x as u8 * 6, y as u8 * 20, 128),
+                1 => RGB::new(40, 40, 40),
+                _ => RGB::new((seed >> 24) as u8, (seed >> 16) as u8, (seed >> 8) as u8),
+            };
+            assert!(img.set_pixel(x, y, c));
+        }
+    }
+    round_trip(&img);
+    round_trip(&PPM::new(1, 1));
+
+    // noise does not compress : several IDAT chunks and far matches
+    let mut big = PPM::new(300, 120);
+    for y in 0..120 {
+        for x in 0..300 {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let c = if x < 240 { RGB::new((seed >> 24) as u8, (seed >> 16) as u8, x as u8) } else { RGB::new(1, 2, 3) };
+            assert!(big.set_pixel(x, y, c));
+        }
+    }
+    round_trip(&big);
+}
@@ -0,0 +1,127 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process;
+
+use raytracer::PPM::{ImageFormat, Overwrite, PPM, RGB};
+
+fn gradient(w: u32, h: u32) -> PPM {
+    let mut img = PPM::new(w, h);
+    for x in 0..w {
+        for y in 0..h {
+            assert!(img.set_pixel(x, y, RGB::new(x as u8, y as u8, (x * 7 + y) as u8)));
+        }
+    }
+    img
+}
+
+#[test]
+fn header_is_width_then_height() {
+    let mut out = Vec::new();
+    gradient(5, 2).write_to(&mut out).unwrap();
+    assert!(out.starts_with(b"P6 5 2 255\n"));
+    assert_eq!(out.len(), "P6 5 2 255\n".len() + 5 * 2 * 3);
+}
+
+#[test]
+fn wide_and_tall_images_round_trip() {
+    for &(w, h) in [(7, 3), (3, 7), (1, 9), (16, 1)].iter() {
+        let img = gradient(w, h);
+        let mut out = Vec::new();
+        img.write_to(&mut out).unwrap();
+
+        let back = PPM::read_from(&out[..]).unwrap();
+        assert_eq!((back.width(), back.height()), (w, h));
+        for x in 0..w {
+            for y in 0..h {
+                assert_eq!(back.get_pixel(x, y), img.get_pixel(x, y), "pixel ({}, {}) of {}x{}", x, y, w, h);
+            }
+        }
+    }
+}
+
+#[test]
+fn pixels_outside_the_image_are_rejected() {
+    let mut img = PPM::new(4, 2);
+    assert!(!img.set_pixel(4, 0, RGB::new(1, 2, 3)));
+    assert!(!img.set_pixel(0, 2, RGB::new(1, 2, 3)));
+    assert!(img.get_pixel(4, 1).is_none());
+    assert_eq!(img.get_pixel(3, 1), Some(RGB::new(0, 0, 0)));
+}
+
+#[test]
+fn header_comments_are_skipped() {
+    let mut file = b"P6\n# made by hand\n2 1\n255\n".to_vec();
+    file.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+    let img = PPM::read_from(&file[..]).unwrap();
+    assert_eq!(img.get_pixel(1, 0), Some(RGB::new(4, 5, 6)));
+    assert!(PPM::read_from(&file[..file.len() - 1]).is_err());
+}
+
+// an empty directory of its own for each test
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("raytracer-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn filled(c: u8) -> PPM {
+    let mut img = PPM::new(4, 4);
+    for x in 0..4 {
+        for y in 0..4 {
+            img.set_pixel(x, y, RGB::new(c, c, c));
+        }
+    }
+    img
+}
+
+#[test]
+fn existing_files_are_kept_unless_allowed() {
+    let dir = temp_dir("overwrite");
+    let path = dir.join("out.ppm");
+    fs::write(&path, b"keep me").unwrap();
+
+    let e = filled(1).save_to(&path, Overwrite::Deny).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::AlreadyExists);
+    assert_eq!(fs::read(&path).unwrap(), b"keep me");
+
+    filled(2).save_to(&path, Overwrite::Allow).unwrap();
+    let mut expected = Vec::new();
+    filled(2).write_to(&mut expected).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), expected);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn pictures_take_the_next_free_number() {
+    let dir = temp_dir("numbers");
+    for name in ["Picture0.ppm", "Picture1.ppm", "Picture3.ppm"].iter() {
+        fs::write(dir.join(name), b"older").unwrap();
+    }
+    assert_eq!(filled(1).save_file_in(&dir, ImageFormat::Ppm).unwrap(), dir.join("Picture2.ppm"));
+    assert_eq!(filled(1).save_file_in(&dir, ImageFormat::Ppm).unwrap(), dir.join("Picture4.ppm"));
+    assert_eq!(fs::read(dir.join("Picture1.ppm")).unwrap(), b"older");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unwritable_paths_are_errors() {
+    let dir = temp_dir("unwritable");
+    let missing = dir.join("no/such/dir");
+    assert!(filled(1).save_to(missing.join("out.ppm"), Overwrite::Allow).is_err());
+    assert!(filled(1).save_to(missing.join("out.ppm"), Overwrite::Deny).is_err());
+    // no endless search for a free name either
+    assert_eq!(filled(1).save_file_in(&missing, ImageFormat::Ppm).unwrap_err().kind(), ErrorKind::NotFound);
+    assert!(!missing.exists());
+
+    // the binary stops with an error instead of going on
+    let out = process::Command::new(env!("CARGO_BIN_EXE_raytracer"))
+        .args(["--width", "4", "--height", "4", "-o"].iter())
+        .arg(missing.join("out.ppm"))
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).starts_with("cannot save the image: "));
+    fs::remove_dir_all(&dir).unwrap();
+}
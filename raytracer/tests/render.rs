@@ -0,0 +1,74 @@
+use raytracer::hdr::{Framebuffer, RGBf};
+use raytracer::render::{render, render_tiles, Adaptive, Settings};
+use raytracer::scene_file::SceneFile;
+
+fn render_with(threads: usize, tile: u32) -> Framebuffer {
+    let SceneFile { scene, camera } = SceneFile::parse(include_str!("../scenes/default.toml")).unwrap();
+    let settings = Settings {
+        width: 97,
+        height: 61,
+        samples: 4,
+        threads,
+        tile_size: tile,
+        ..Settings::default()
+    };
+    render(&scene, &camera.camera(97, 61).unwrap(), &settings)
+}
+
+fn bits(fb: &Framebuffer) -> Vec<[u32; 3]> {
+    fb.pixels().iter().map(|c: &RGBf| [c.r.to_bits(), c.g.to_bits(), c.b.to_bits()]).collect()
+}
+
+#[test]
+fn threads_and_tiles_do_not_change_the_picture() {
+    let serial = bits(&render_with(1, 16));
+    assert!(serial.iter().any(|p| *p != [0; 3]), "the scene should not be empty");
+    for &(threads, tile) in [(2, 16), (4, 7), (8, 32), (3, 1000)].iter() {
+        assert!(bits(&render_with(threads, tile)) == serial, "{} threads, tiles of {}", threads, tile);
+    }
+}
+
+#[test]
+fn every_pixel_is_rendered_once_at_its_place() {
+    let fb = render_tiles(45, 20, 8, 4, |x, y| RGBf::new(x as f32, y as f32, 1.0));
+    for y in 0..20 {
+        for x in 0..45 {
+            assert_eq!(fb.get_pixel(x, y), Some(RGBf::new(x as f32, y as f32, 1.0)));
+        }
+    }
+}
+
+fn render_adaptive(adaptive: Adaptive, threads: usize) -> Framebuffer {
+    let SceneFile { scene, camera } = SceneFile::parse(include_str!("../scenes/default.toml")).unwrap();
+    let settings = Settings {
+        width: 97,
+        height: 61,
+        samples: 16,
+        adaptive: Some(adaptive),
+        threads,
+        ..Settings::default()
+    };
+    render(&scene, &camera.camera(97, 61).unwrap(), &settings)
+}
+
+#[test]
+fn adaptive_sampling_refines_edges_only() {
+    let SceneFile { scene, camera } = SceneFile::parse(include_str!("../scenes/default.toml")).unwrap();
+    let full = Settings { width: 97, height: 61, samples: 16, threads: 1, ..Settings::default() };
+    let full = bits(&render(&scene, &camera.camera(97, 61).unwrap(), &full));
+
+    // below any possible difference every pixel is refined ; new() would
+    // not take it
+    assert!(bits(&render_adaptive(Adaptive { initial: 4, threshold: -1.0 }, 1)) == full);
+
+    let a = Adaptive::new(0.05).unwrap();
+    let adaptive = render_adaptive(a, 1);
+    assert!(bits(&adaptive) == bits(&render_adaptive(a, 3)));
+    let refined = bits(&adaptive).iter().zip(&full).filter(|(a, b)| a == b).count();
+    assert!(refined > 0 && refined < full.len(), "{} of {} pixels refined", refined, full.len());
+    // far from every object the background is left to the first pass
+    assert_eq!(adaptive.get_pixel(0, 0), Some(Settings::default().background));
+
+    assert!(Adaptive::new(-0.1).is_none() && Adaptive::new(f32::NAN).is_none());
+    assert_eq!(Adaptive::new(0.0).map(|a| a.initial), Some(4));
+}
@@ -0,0 +1,93 @@
+use raytracer::sampling::{concentric_disk, Filter, Pattern, Rng};
+
+const PATTERNS: [Pattern; 4] = [Pattern::Grid, Pattern::Jittered, Pattern::Halton, Pattern::Sobol];
+
+fn points(p: Pattern, n: u32, x: u32, y: u32) -> Vec<(f32, f32)> {
+    p.samples(n, Rng::for_pixel(x, y)).collect()
+}
+
+#[test]
+fn samples_stay_in_the_unit_square() {
+    for &p in PATTERNS.iter() {
+        for &n in [1, 4, 9, 16, 64].iter() {
+            for px in 0..20 {
+                let pts = points(p, n, px, 3 * px + 1);
+                assert_eq!(pts.len(), n as usize);
+                assert!(pts.iter().all(|&(a, b)| (0.0..1.0).contains(&a) && (0.0..1.0).contains(&b)),
+                    "{:?} {} {:?}", p, n, pts);
+            }
+        }
+    }
+    // and the same ones for the same pixel
+    assert_eq!(points(Pattern::Jittered, 16, 5, 7), points(Pattern::Jittered, 16, 5, 7));
+    assert!(points(Pattern::Jittered, 16, 5, 7) != points(Pattern::Jittered, 16, 6, 7));
+}
+
+#[test]
+fn grids_keep_one_sample_per_cell() {
+    assert_eq!(points(Pattern::Grid, 4, 0, 0), [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]);
+    for (i, (a, b)) in points(Pattern::Jittered, 9, 2, 2).into_iter().enumerate() {
+        assert_eq!(((a * 3.0) as usize, (b * 3.0) as usize), (i % 3, i / 3));
+    }
+}
+
+#[test]
+fn sequences_start_with_their_known_points() {
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-6;
+
+    // Halton in bases 2 and 3, up to the shift of the pixel
+    let halton = [(0.0, 0.0), (0.5, 1.0 / 3.0), (0.25, 2.0 / 3.0), (0.75, 1.0 / 9.0), (0.125, 4.0 / 9.0)];
+    let pts = points(Pattern::Halton, 5, 4, 9);
+    for (&(a, b), &(ha, hb)) in pts.iter().zip(halton.iter()) {
+        assert!(close((a - pts[0].0).rem_euclid(1.0), ha) && close((b - pts[0].1).rem_euclid(1.0), hb));
+    }
+
+    // Sobol, up to the scrambling of the digits : xor with the first point
+    let sobol = [(0.0, 0.0), (0.5, 0.5), (0.25, 0.75), (0.75, 0.25), (0.125, 0.625), (0.625, 0.125),
+        (0.375, 0.375), (0.875, 0.875)];
+    let bits = |v: f32| (v * (1u32 << 24) as f32) as u32;
+    let pts = points(Pattern::Sobol, 8, 11, 2);
+    for (&(a, b), &(sa, sb)) in pts.iter().zip(sobol.iter()) {
+        assert_eq!(bits(a) ^ bits(pts[0].0), bits(sa));
+        assert_eq!(bits(b) ^ bits(pts[0].1), bits(sb));
+    }
+    // 16 of them fill every cell of a 4 x 4 grid
+    let mut cells = [0; 16];
+    for (a, b) in points(Pattern::Sobol, 16, 1, 1) {
+        cells[(a * 4.0) as usize + 4 * (b * 4.0) as usize] += 1;
+    }
+    assert_eq!(cells, [1; 16]);
+}
+
+#[test]
+fn filters_peak_at_the_center_and_vanish_at_their_radius() {
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-6;
+    assert_eq!(Filter::Box.weight(0.0, 0.0), 1.0);
+    assert_eq!(Filter::Box.weight(0.5, -0.5), 1.0);
+    assert_eq!(Filter::Tent.weight(0.0, 0.0), 1.0);
+    assert!(close(Filter::Gaussian.weight(0.0, 0.0), (1.0 - (-4.5f32).exp()).powi(2)));
+    assert!(close(Filter::Mitchell.weight(0.0, 0.0), (8.0f32 / 9.0).powi(2)));
+
+    for &f in [Filter::Tent, Filter::Gaussian, Filter::Mitchell].iter() {
+        let r = f.radius();
+        assert!(f.weight(r, 0.0).abs() < 1e-6 && f.weight(0.0, -r).abs() < 1e-6, "{:?}", f);
+        assert_eq!(f.weight(0.3, -0.2), f.weight(-0.3, 0.2));
+        assert!(f.weight(0.0, 0.0) > f.weight(0.4, 0.0));
+    }
+    assert!(close(Filter::Tent.weight(0.5, 0.5), 0.25));
+    // Mitchell has a negative lobe between 1 and 2
+    assert!(Filter::Mitchell.weight(1.5, 0.0) < 0.0);
+}
+
+#[test]
+fn disk_samples_stay_on_the_disk() {
+    assert_eq!(concentric_disk(0.5, 0.5), (0.0, 0.0));
+    let mut rng = Rng::new(3);
+    for _ in 0..1000 {
+        let (x, y) = concentric_disk(rng.next_f32(), rng.next_f32());
+        assert!(x * x + y * y <= 1.0 + 1e-6);
+    }
+    // the edges of the square go to the rim
+    let (x, y) = concentric_disk(1.0, 0.5);
+    assert!((x - 1.0).abs() < 1e-6 && y.abs() < 1e-6);
+}
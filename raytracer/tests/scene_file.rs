@@ -0,0 +1,110 @@
+use std::fs;
+use std::path::Path;
+
+use raytracer::render::{render, Settings};
+use raytracer::scene_file::SceneFile;
+
+// a valid sphere on lines 1 to 7
+const SPHERE: &str = "[[sphere]]\ncenter = [1, 0, 0]\nradius = 0.5\nsurface = \"solid\"\n\
+    color = [255, 0, 0]\nspecular = 1\nshininess = 10\n";
+
+fn err(text: &str) -> String {
+    SceneFile::parse(text).err().expect(text).to_string()
+}
+
+fn sphere_with(key: &str, value: &str) -> String {
+    SPHERE.lines()
+        .map(|l| if l.starts_with(key) { format!("{} = {}", key, value) } else { l.to_string() })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn values_are_checked_on_their_line() {
+    assert!(SceneFile::parse(SPHERE).is_ok());
+    let cases = [
+        (sphere_with("radius", "0"), "line 3: sphere radius must be positive, got 0"),
+        (sphere_with("radius", "-1"), "line 3: sphere radius must be positive, got -1"),
+        (sphere_with("radius", "\"big\""), "line 3: `radius` must be a number, got a string"),
+        (sphere_with("radius", "abc"), "line 3: bad value `abc`"),
+        (sphere_with("center", "[1, 0]"), "line 2: `center` must be an array of 3 numbers"),
+        (sphere_with("center", "[1, 0 0]"), "line 2: expected `,` or `]` in array"),
+        (sphere_with("center", "[1, 0, 0] 2"), "line 2: unexpected `2` after value"),
+        (sphere_with("surface", "\"solid"), "line 4: unterminated string"),
+        (sphere_with("surface", "\"so\\lid\""), "line 4: bad escape in string"),
+        (sphere_with("surface", "\"glass\""),
+            "line 4: unknown surface \"glass\", expected \"solid\", \"mirror\" or \"transparent\""),
+        (sphere_with("color", "[256, 0, 0]"), "line 5: `color` components must be integers in 0..=255, got 256"),
+        (sphere_with("specular", "-1"), "line 6: `specular` must not be negative, got -1"),
+    ];
+    for (text, msg) in cases.iter() {
+        assert_eq!(err(text), *msg);
+    }
+}
+
+#[test]
+fn keys_and_tables_are_checked() {
+    let cases = [
+        (format!("{}colour = [1, 2, 3]\n", SPHERE), "line 8: unknown key `colour` in [sphere]"),
+        (format!("{}radius = 1\n", SPHERE), "line 8: duplicate key `radius`"),
+        ("[[sphere]]\ncenter = [1, 0, 0]\n".to_string(), "line 1: missing `radius` in [sphere]"),
+        ("[[sphere]]\ncenter\n".to_string(), "line 2: expected `key = value`, got `center`"),
+        ("[[sphere]]\ncen-ter = 1\n".to_string(), "line 2: bad key `cen-ter`"),
+        ("x = 1\n".to_string(), "line 1: `x` is outside of any table"),
+        ("[camera\n".to_string(), "line 1: malformed table header `[camera`"),
+        ("[[a b]]\n".to_string(), "line 1: bad table name `a b`"),
+        ("[[cube]]\n".to_string(), "line 1: unknown table [[cube]]"),
+        ("[sphere]\n".to_string(), "line 1: unknown table [sphere]"),
+        ("[camera]\n\n[camera]\n".to_string(), "line 3: duplicate [camera] table"),
+        ("[camera]\nfov = 200\n".to_string(), "line 2: `fov` must be between 0 and 180 degrees, got 200"),
+        ("[camera]\nposition = [0, 0, 0]\nlook_at = [0, 0, 0]\n".to_string(),
+            "line 1: `look_at` must differ from `position` and `up` must not point along the view"),
+    ];
+    for (text, msg) in cases.iter() {
+        assert_eq!(err(text), *msg);
+    }
+}
+
+#[test]
+fn arrays_span_lines_and_comments_are_skipped() {
+    let text = "# a red ball\n[[sphere]]   # the only one\ncenter = [1,\n    0,   # y\n    0]\n\
+        radius = 1\nsurface = \"so#lid\"\n";
+    // the lines of an array are counted, `#` in a string is no comment
+    assert_eq!(err(text),
+        "line 7: unknown surface \"so#lid\", expected \"solid\", \"mirror\" or \"transparent\"");
+    let text = "[[sphere]]\nradius = 1\ncenter = [1,\n 0,\n";
+    assert_eq!(err(text), "line 3: unclosed array in `center`");
+
+    let err = SceneFile::load("no/such/scene.toml").err().unwrap().to_string();
+    assert!(err.starts_with("cannot read scene: "), "{}", err);
+}
+
+// every scene shipped loads, and the same text without its comments and
+// blank lines renders the same picture
+#[test]
+fn shipped_scenes_round_trip() {
+    let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/scenes"));
+    let settings = Settings { width: 12, height: 8, threads: 1, ..Settings::default() };
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "toml") {
+            continue;
+        }
+        let loaded = SceneFile::load(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let text = fs::read_to_string(&path).unwrap();
+        let bare: String = text.lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            // model paths are relative to the scene file
+            .map(|l| l.replace("file = \"", &format!("file = \"{}/", dir.display())) + "\n")
+            .collect();
+        let parsed = SceneFile::parse(&bare).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+        let a = render(&loaded.scene, &loaded.camera.camera(12, 8).unwrap(), &settings);
+        let b = render(&parsed.scene, &parsed.camera.camera(12, 8).unwrap(), &settings);
+        assert!(a.pixels() == b.pixels(), "{}", path.display());
+        count += 1;
+    }
+    assert!(count >= 2);
+}
@@ -0,0 +1,85 @@
+use raytracer::bvh::{Aabb, Bvh};
+use raytracer::objects::{Light, LightOptions, Quad, Scene, Shape, Sphere};
+use raytracer::sampling::Rng;
+
+mod common;
+use common::{v, white};
+
+// a floor at z = 0 made of two quads meeting at y = 0, lit from above
+fn floor() -> Scene {
+    let mut scene = Scene::new();
+    scene.add_obj(Quad::new(v(-5.0, -5.0, 0.0), v(10.0, 0.0, 0.0), v(0.0, 5.0, 0.0), white()).unwrap());
+    scene.add_obj(Quad::new(v(-5.0, 0.0, 0.0), v(10.0, 0.0, 0.0), v(0.0, 5.0, 0.0), white()).unwrap());
+    scene.add_light(Light::new(v(0.0, 3.0, 4.0), LightOptions::new(0.0, 1.0, 0.0).with_intensity(25.0)));
+    scene
+}
+
+#[test]
+fn occlusion_only_counts_what_lies_in_between() {
+    let mut scene = floor();
+    scene.add_obj(Sphere::new(v(0.0, 0.0, 2.0), 0.5, white()).unwrap());
+    assert!(scene.occluded(v(0.0, 0.0, 0.001), v(0.0, 0.0, 4.0)));
+    assert!(!scene.occluded(v(0.0, 0.0, 0.001), v(0.0, 0.0, 1.0)));
+    assert!(!scene.occluded(v(0.0, 0.0, 3.0), v(0.0, 0.0, 4.0)));
+    assert!(!scene.occluded(v(0.0, 0.0, 4.0), v(0.0, 0.0, 4.0)));
+    // below the floor
+    assert!(scene.occluded(v(0.0, 0.0, -1.0), v(0.0, 3.0, 4.0)));
+}
+
+#[test]
+fn seams_between_shapes_are_lit() {
+    let scene = floor();
+    let eye = v(0.0, -3.0, 3.0);
+    let lit = scene.radiance_on_dir(eye, v(0.3, 0.01, 0.0) - eye).unwrap();
+    assert!(lit.r > 0.3);
+    for i in -20..=20 {
+        let target = v(0.3, i as f32 * 1e-4, 0.0);
+        let c = scene.radiance_on_dir(eye, target - eye).unwrap();
+        assert!((c.r - lit.r).abs() < 0.01, "unlit at y = {}", target.y);
+    }
+}
+
+#[test]
+fn casters_beyond_the_light_cast_nothing() {
+    let eye = v(0.0, -3.0, 3.0);
+    let target = v(0.5, 0.5, 0.0);
+    let open = floor().radiance_on_dir(eye, target - eye).unwrap();
+
+    let mut scene = floor();
+    scene.add_obj(Sphere::new(v(0.0, 3.5, 5.0), 0.5, white()).unwrap());
+    assert_eq!(scene.radiance_on_dir(eye, target - eye).unwrap(), open);
+
+    let mut scene = floor();
+    scene.add_obj(Sphere::new(v(0.25, 1.75, 2.0), 0.5, white()).unwrap());
+    assert!(scene.radiance_on_dir(eye, target - eye).unwrap().r < open.r);
+}
+
+#[test]
+fn faces_turned_from_the_light_are_dark() {
+    let mut scene = Scene::new();
+    scene.add_obj(Sphere::new(v(0.0, 0.0, 0.0), 1.0, white()).unwrap());
+    scene.add_light(Light::new(v(0.0, 0.0, 5.0), LightOptions::new(50.0, 100.0, 0.0)));
+    let top = scene.radiance_on_dir(v(-5.0, 0.0, 0.5), v(1.0, 0.0, 0.0)).unwrap();
+    let bottom = scene.radiance_on_dir(v(-5.0, 0.0, -0.5), v(1.0, 0.0, 0.0)).unwrap();
+    assert!(top.r > 0.0);
+    assert_eq!(bottom.r, 0.0);
+}
+
+#[test]
+fn any_hit_agrees_with_the_nearest_hit() {
+    let mut rng = Rng::new(11);
+    let spheres: Vec<Sphere> = (0..300)
+        .map(|_| Sphere::new(v(rng.next_f32() * 10.0, rng.next_f32() * 10.0, rng.next_f32() * 10.0), 0.3, white()).unwrap())
+        .collect();
+    let boxes: Vec<Aabb> = spheres.iter().map(|s| Shape::bounds(s).unwrap()).collect();
+    let bvh = Bvh::new(&boxes);
+    for _ in 0..1000 {
+        let from = v(rng.next_f32() * 10.0, rng.next_f32() * 10.0, rng.next_f32() * 10.0);
+        let to = v(rng.next_f32() * 10.0, rng.next_f32() * 10.0, rng.next_f32() * 10.0);
+        let dist = (to - from).len();
+        let dir = (to - from).norm();
+        let hit = |i: usize| Shape::ray_intersect(&spheres[i], from, dir);
+        let nearest = bvh.nearest(from, dir, hit).is_some_and(|(_, t)| t < dist);
+        assert_eq!(bvh.any_hit(from, dir, dist, hit), nearest);
+    }
+}
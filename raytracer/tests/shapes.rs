@@ -0,0 +1,154 @@
+use raytracer::objects::{Light, LightOptions, Scene, Shape};
+use raytracer::shapes::{Cone, Cuboid, Cylinder, Disk, Torus};
+use raytracer::scene_file::SceneFile;
+
+mod common;
+use common::{close, matte, v};
+
+#[test]
+fn box_faces_and_inside() {
+    let b = Cuboid::new(v(1.0, -1.0, -1.0), v(3.0, 1.0, 1.0), matte()).unwrap();
+    let hit = b.intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - 1.0).abs() < 1e-5);
+    assert!(close(hit.normal, v(-1.0, 0.0, 0.0)));
+    assert!(hit.front_face);
+
+    let hit = b.intersect(v(2.0, 0.0, 5.0), v(0.0, 0.0, -1.0)).unwrap();
+    assert!((hit.t - 4.0).abs() < 1e-5);
+    assert!(close(hit.normal, v(0.0, 0.0, 1.0)));
+
+    // from inside the far wall is hit, its normal turned back to the ray
+    let hit = b.intersect(v(2.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - 1.0).abs() < 1e-5);
+    assert!(close(hit.normal, v(-1.0, 0.0, 0.0)));
+    assert!(!hit.front_face);
+
+    assert!(b.ray_intersect(v(0.0, 2.0, 0.0), v(1.0, 0.0, 0.0)).is_none());
+    assert!(Cuboid::new(v(0.0, 0.0, 0.0), v(1.0, 0.0, 1.0), matte()).is_none());
+}
+
+#[test]
+fn turned_box_follows_its_axes() {
+    let s = 2f32.sqrt();
+    let b = Cuboid::oriented(v(3.0, 0.0, 0.0), v(s, s, 1.0), v(1.0, 1.0, 0.0), v(-1.0, 1.0, 0.0), matte()).unwrap();
+    // a diamond seen from its corner
+    let hit = b.intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - 2.0).abs() < 1e-4);
+    assert!(b.ray_intersect(v(0.0, 0.9, 0.0), v(1.0, 0.0, 0.0)).is_some());
+    assert!(b.ray_intersect(v(0.0, 1.1, 0.0), v(1.0, 0.0, 0.0)).is_none());
+    assert!(Cuboid::oriented(v(0.0, 0.0, 0.0), v(1.0, 1.0, 1.0), v(1.0, 0.0, 0.0), v(2.0, 0.0, 0.0), matte()).is_none());
+}
+
+#[test]
+fn cylinder_side_and_caps() {
+    let c = Cylinder::new(v(2.0, 0.0, -1.0), v(2.0, 0.0, 1.0), 0.5, matte()).unwrap();
+    let hit = c.intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - 1.5).abs() < 1e-5);
+    assert!(close(hit.normal, v(-1.0, 0.0, 0.0)));
+
+    let hit = c.intersect(v(2.0, 0.2, 3.0), v(0.0, 0.0, -1.0)).unwrap();
+    assert!((hit.t - 2.0).abs() < 1e-5);
+    assert!(close(hit.normal, v(0.0, 0.0, 1.0)));
+    let hit = c.intersect(v(2.0, 0.2, -3.0), v(0.0, 0.0, 1.0)).unwrap();
+    assert!(close(hit.normal, v(0.0, 0.0, -1.0)));
+
+    assert!(c.ray_intersect(v(0.0, 0.0, 1.5), v(1.0, 0.0, 0.0)).is_none());
+    assert!(c.ray_intersect(v(0.0, 0.6, 0.0), v(1.0, 0.0, 0.0)).is_none());
+    assert!(Cylinder::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, 0.0), 1.0, matte()).is_none());
+}
+
+#[test]
+fn cone_narrows_to_its_apex() {
+    let c = Cone::new(v(2.0, 0.0, -1.0), v(2.0, 0.0, 1.0), 1.0, matte()).unwrap();
+    // half way up the radius is 0.5
+    let hit = c.intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - 1.5).abs() < 1e-4);
+    let n = v(-1.0, 0.0, 0.5).norm();
+    assert!(close(hit.normal, n));
+    assert!(c.ray_intersect(v(0.0, 0.6, 0.0), v(1.0, 0.0, 0.0)).is_none());
+
+    let hit = c.intersect(v(2.5, 0.0, -3.0), v(0.0, 0.0, 1.0)).unwrap();
+    assert!((hit.t - 2.0).abs() < 1e-5);
+    assert!(close(hit.normal, v(0.0, 0.0, -1.0)));
+    // the other nappe of the double cone is not part of it
+    assert!(c.ray_intersect(v(0.0, 0.0, 2.0), v(1.0, 0.0, 0.0)).is_none());
+}
+
+#[test]
+fn disk_is_a_round_window() {
+    let d = Disk::new(v(2.0, 0.0, 0.0), v(-1.0, 0.0, 0.0), 1.0, matte()).unwrap();
+    assert_eq!(d.ray_intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), Some(2.0));
+    assert!(d.ray_intersect(v(0.0, 0.7, 0.7), v(1.0, 0.0, 0.0)).is_some());
+    assert!(d.ray_intersect(v(0.0, 0.8, 0.8), v(1.0, 0.0, 0.0)).is_none());
+    let hit = d.intersect(v(4.0, 0.0, 0.0), v(-1.0, 0.0, 0.0)).unwrap();
+    assert!(close(hit.normal, v(1.0, 0.0, 0.0)));
+    assert!(!hit.front_face);
+    assert!(Disk::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, 0.0), 1.0, matte()).is_none());
+}
+
+#[test]
+fn torus_has_a_hole() {
+    let t = Torus::new(v(3.0, 0.0, 0.0), v(0.0, 0.0, 1.0), 1.0, 0.25, matte()).unwrap();
+    // through the tube, then through the hole
+    let hit = t.intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - 1.75).abs() < 1e-3);
+    assert!(close(hit.normal, v(-1.0, 0.0, 0.0)));
+    assert!(t.ray_intersect(v(3.0, 0.0, 3.0), v(0.0, 0.0, -1.0)).is_none());
+
+    let hit = t.intersect(v(4.0, 0.0, 3.0), v(0.0, 0.0, -1.0)).unwrap();
+    assert!((hit.t - 2.75).abs() < 1e-3);
+    assert!(close(hit.normal, v(0.0, 0.0, 1.0)));
+
+    // grazing the top of the tube
+    assert!(t.ray_intersect(v(0.0, 0.0, 0.2), v(1.0, 0.0, 0.0)).is_some());
+    assert!(t.ray_intersect(v(0.0, 0.0, 0.3), v(1.0, 0.0, 0.0)).is_none());
+
+    assert!(Torus::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, 1.0), 1.0, 1.0, matte()).is_none());
+    assert!(Torus::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, 0.0), 1.0, 0.5, matte()).is_none());
+}
+
+#[test]
+fn shapes_cast_shadows() {
+    let mut scene = Scene::new();
+    scene.add_obj(Disk::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, 1.0), 10.0, matte()).unwrap());
+    scene.add_obj(Torus::new(v(0.0, 0.0, 1.0), v(0.0, 0.0, 1.0), 0.6, 0.3, matte()).unwrap());
+    scene.add_light(Light::new(v(0.6, 0.0, 5.0), LightOptions::new(0.0, 1.0, 0.0)));
+
+    // the floor under the light is behind the tube
+    let lit = scene.radiance_on_dir(v(3.0, -3.0, 3.0), v(0.0, 1.0, -1.0).norm()).unwrap();
+    let shaded = scene.radiance_on_dir(v(0.6, -3.0, 3.0), v(0.0, 1.0, -1.0).norm()).unwrap();
+    assert!(lit.r > shaded.r);
+}
+
+#[test]
+fn shape_tables_are_parsed_and_checked() {
+    let surface = "surface = \"mirror\"\nspecular = 1\nshininess = 1\n";
+    let tables = [
+        "[[box]]\nmin = [1, -1, -1]\nmax = [3, 1, 1]\n",
+        "[[box]]\ncenter = [2, 0, 0]\nsize = [2, 2, 2]\nx_axis = [1, 1, 0]\ny_axis = [-1, 1, 0]\n",
+        "[[cylinder]]\nbase = [2, 0, -1]\ntop = [2, 0, 1]\nradius = 0.5\n",
+        "[[cone]]\nbase = [2, 0, -1]\napex = [2, 0, 1]\nradius = 0.5\n",
+        "[[disk]]\ncenter = [2, 0, 0]\nnormal = [-1, 0, 0]\nradius = 0.5\n",
+        "[[torus]]\ncenter = [2, 0, 0]\naxis = [1, 0, 0]\nmajor = 0.5\nminor = 0.2\n",
+    ];
+    for t in tables.iter() {
+        let scene = SceneFile::parse(&format!("{}{}", t, surface)).unwrap_or_else(|e| panic!("{}: {}", t, e)).scene;
+        // every one stands across the x axis, the torus around it
+        let aim = if t.starts_with("[[torus]]") { v(0.0, 0.5, 0.0) } else { v(0.0, 0.0, 0.0) };
+        assert_eq!(scene.ind_viewing(aim, v(1.0, 0.0, 0.0)), Some(0), "{}", t);
+        assert_eq!(scene.ind_viewing(aim, v(-1.0, 0.0, 0.0)), None, "{}", t);
+    }
+
+    let cases = [
+        ("[[box]]\nmin = [1, 1, 1]\nmax = [1, 2, 2]\n",
+            "line 1: a box needs `max` above `min` (or a positive `size`) and axes that are not parallel"),
+        ("[[cylinder]]\nbase = [0, 0, 0]\ntop = [0, 0, 0]\nradius = 1\n", "line 1: cylinder `top` must differ from `base`"),
+        ("[[cone]]\nbase = [0, 0, 0]\napex = [0, 0, 0]\nradius = 1\n", "line 1: cone `apex` must differ from `base`"),
+        ("[[disk]]\ncenter = [0, 0, 0]\nnormal = [0, 0, 0]\nradius = 1\n", "line 1: disk `normal` must not be zero"),
+        ("[[torus]]\ncenter = [0, 0, 0]\nmajor = 0.2\nminor = 0.5\n",
+            "line 1: torus needs a non-zero `axis` and `minor` below `major`"),
+    ];
+    for (t, msg) in cases.iter() {
+        assert_eq!(SceneFile::parse(&format!("{}{}", t, surface)).err().unwrap().to_string(), *msg);
+    }
+}
@@ -0,0 +1,85 @@
+use raytracer::hdr::RGBf;
+use raytracer::tonemap::{Operator, ToneMap, Transfer};
+
+fn gray(v: f32) -> RGBf {
+    RGBf::new(v, v, v)
+}
+
+fn operators() -> [Operator; 5] {
+    [
+        Operator::Clamp,
+        Operator::Reinhard { white: f32::INFINITY },
+        Operator::Reinhard { white: 4.0 },
+        Operator::Aces,
+        Operator::Filmic,
+    ]
+}
+
+#[test]
+fn operators_are_monotonic_and_stay_in_range() {
+    let transfers = [Transfer::Linear, Transfer::Srgb, Transfer::Gamma(2.2)];
+    for &op in operators().iter() {
+        for &tf in transfers.iter() {
+            let tm = ToneMap::new(0.0, op, tf);
+            let mut last = -1.0f32;
+            let mut last_rgb = 0u8;
+            for i in 0..=2000 {
+                // from below black to far above white
+                let v = (i as f32 - 10.0) * 0.05;
+                let out = tm.apply(gray(v));
+                assert!(out.r >= 0.0 && out.r <= 1.0, "{:?} {:?} at {}: {}", op, tf, v, out.r);
+                assert!(out.r >= last, "{:?} {:?} goes down at {}", op, tf, v);
+                assert_eq!((out.r, out.g), (out.b, out.b));
+                let rgb = tm.to_rgb(gray(v)).r;
+                assert!(rgb >= last_rgb);
+                last = out.r;
+                last_rgb = rgb;
+            }
+            assert_eq!(tm.to_rgb(gray(-1.0)).r, 0);
+            assert_eq!(tm.to_rgb(gray(0.0)).r, 0);
+        }
+    }
+}
+
+#[test]
+fn curves_meet_their_anchors() {
+    let linear = |op: Operator, v: f32| ToneMap::new(0.0, op, Transfer::Linear).apply(gray(v)).r;
+    assert_eq!(linear(Operator::Clamp, 0.3), 0.3);
+    assert_eq!(linear(Operator::Clamp, 7.0), 1.0);
+    // L / (1 + L), and white at `white`
+    assert!((linear(Operator::Reinhard { white: f32::INFINITY }, 1.0) - 0.5).abs() < 1e-6);
+    assert!((linear(Operator::Reinhard { white: 4.0 }, 4.0) - 1.0).abs() < 1e-6);
+    // the filmic white point is 11.2 once doubled
+    assert!((linear(Operator::Filmic, 5.6) - 1.0).abs() < 1e-5);
+    assert!(linear(Operator::Aces, 0.0).abs() < 1e-6 && linear(Operator::Filmic, 0.0).abs() < 1e-6);
+    // the curves compress : highlights come out darker than clipped
+    for &op in operators()[1..].iter() {
+        assert!(linear(op, 0.9) < 0.9, "{:?}", op);
+    }
+}
+
+#[test]
+fn exposure_and_gamma_move_values_as_documented() {
+    for &op in operators().iter() {
+        let at = |ev: f32| ToneMap::new(ev, op, Transfer::Linear).apply(gray(0.2)).r;
+        assert!(at(1.0) > at(0.0) && at(0.0) > at(-1.0), "{:?}", op);
+    }
+    // one stop doubles the light
+    let clamp = |ev: f32| ToneMap::new(ev, Operator::Clamp, Transfer::Linear).apply(gray(0.2)).r;
+    assert!((clamp(1.0) - 0.4).abs() < 1e-6 && (clamp(-2.0) - 0.05).abs() < 1e-6);
+
+    // encoding for a display lifts the mid tones, ends stay put
+    let encode = |tf: Transfer, v: f32| ToneMap::new(0.0, Operator::Clamp, tf).apply(gray(v)).r;
+    assert!((encode(Transfer::Srgb, 0.5) - 0.735_357).abs() < 1e-4);
+    assert!((encode(Transfer::Gamma(2.0), 0.25) - 0.5).abs() < 1e-6);
+    assert!(encode(Transfer::Gamma(2.2), 0.5) > encode(Transfer::Linear, 0.5));
+    assert!(encode(Transfer::Gamma(2.8), 0.5) > encode(Transfer::Gamma(1.8), 0.5));
+    for &tf in [Transfer::Srgb, Transfer::Gamma(2.2)].iter() {
+        assert_eq!(encode(tf, 0.0), 0.0);
+        assert!((encode(tf, 1.0) - 1.0).abs() < 1e-6);
+    }
+
+    assert_eq!(Transfer::from_name("2.2"), Some(Transfer::Gamma(2.2)));
+    assert_eq!(Transfer::from_name("-1"), None);
+    assert_eq!(Operator::from_name("ACES"), Some(Operator::Aces));
+}
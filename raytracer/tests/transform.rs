@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use raytracer::III_vector::{Mat4, Transform};
+use raytracer::objects::{Shape, Sphere, TriangleMesh};
+use raytracer::shapes::{Cuboid, Instance};
+use raytracer::scene_file::SceneFile;
+
+mod common;
+use common::{close, matte, v};
+
+#[test]
+fn matrices_compose_and_invert() {
+    let m = Mat4::translation(v(1.0, 2.0, 3.0)) * Mat4::rotation(v(0.0, 0.0, 1.0), 90.0) * Mat4::scaling(v(2.0, 1.0, 1.0));
+    // scaled, turned from +x to +y, then moved
+    assert!(close(m.point(v(1.0, 0.0, 0.0)), v(1.0, 4.0, 3.0)));
+    assert!(close(m.dir(v(1.0, 0.0, 0.0)), v(0.0, 2.0, 0.0)));
+
+    let inv = m.inverse().unwrap();
+    let id = m * inv;
+    for i in 0..4 {
+        for j in 0..4 {
+            let want = if i == j { 1.0 } else { 0.0 };
+            assert!((id.m[i][j] - want).abs() < 1e-5);
+        }
+    }
+    assert!(Mat4::scaling(v(1.0, 0.0, 1.0)).inverse().is_none());
+    assert_eq!(Mat4::identity().transpose(), Mat4::identity());
+}
+
+#[test]
+fn transforms_chain_in_order() {
+    let t = Transform::scale(v(2.0, 2.0, 2.0)).unwrap()
+        .then(Transform::rotate(v(0.0, 0.0, 1.0), 90.0).unwrap())
+        .then(Transform::translate(v(0.0, 0.0, 1.0)));
+    let p = t.point(v(1.0, 0.0, 0.0));
+    assert!(close(p, v(0.0, 2.0, 1.0)));
+    assert!(close(t.inv_point(p), v(1.0, 0.0, 0.0)));
+    assert!(close(t.inverse().point(p), v(1.0, 0.0, 0.0)));
+    assert!(close(t.inv_dir(t.dir(v(0.0, 1.0, 0.0))), v(0.0, 1.0, 0.0)));
+
+    // a 45 degree slope stretched twice along x gets flatter, and its
+    // normal steeper
+    let s = Transform::scale(v(2.0, 1.0, 1.0)).unwrap();
+    let n = s.normal(v(1.0, 0.0, 1.0).norm());
+    assert!(close(n, v(1.0, 0.0, 2.0).norm()));
+    assert!((s.dir(v(1.0, 0.0, -1.0)) * n).abs() < 1e-6);
+    assert!(close(s.inv_normal(n), v(1.0, 0.0, 1.0).norm()));
+
+    assert!(Transform::scale(v(0.0, 1.0, 1.0)).is_none());
+    assert!(Transform::rotate(v(0.0, 0.0, 0.0), 10.0).is_none());
+}
+
+#[test]
+fn scaled_sphere_is_an_ellipsoid() {
+    let unit = Sphere::new(v(0.0, 0.0, 0.0), 1.0, matte()).unwrap();
+    let t = Transform::scale(v(2.0, 1.0, 1.0)).unwrap().then(Transform::translate(v(5.0, 0.0, 0.0)));
+    let e = Instance::new(unit, t);
+
+    let hit = e.intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - 3.0).abs() < 1e-4);
+    assert!(close(hit.point, v(3.0, 0.0, 0.0)));
+    assert!(close(hit.normal, v(-1.0, 0.0, 0.0)));
+
+    // distances stay in world units whatever the scale
+    let hit = e.intersect(v(5.0, 0.0, 4.0), v(0.0, 0.0, -1.0)).unwrap();
+    assert!((hit.t - 3.0).abs() < 1e-4);
+    assert!(e.ray_intersect(v(0.0, 0.0, 1.1), v(1.0, 0.0, 0.0)).is_none());
+
+    // on the slanted side the normal is not the direction from the center
+    let p = v(5.0 + 2.0 * 0.6, 0.0, 0.8);
+    let hit = e.intersect(p + v(0.0, 0.0, 1.0), v(0.0, 0.0, -1.0)).unwrap();
+    assert!(close(hit.point, p));
+    assert!(close(hit.normal, v(0.3, 0.0, 0.8).norm()));
+}
+
+#[test]
+fn turned_box_is_hit_on_its_corner() {
+    let b = Cuboid::new(v(-1.0, -1.0, -1.0), v(1.0, 1.0, 1.0), matte()).unwrap();
+    let t = Transform::rotate(v(0.0, 0.0, 1.0), 45.0).unwrap().then(Transform::translate(v(5.0, 0.0, 0.0)));
+    let hit = Instance::new(b, t).intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap();
+    assert!((hit.t - (5.0 - 2f32.sqrt())).abs() < 1e-4);
+}
+
+#[test]
+fn meshes_are_shared_between_instances() {
+    let tri = TriangleMesh::new(vec![v(0.0, -1.0, -1.0), v(0.0, 1.0, -1.0), v(0.0, 0.0, 1.0)],
+        vec![[0, 1, 2]], None, matte()).unwrap();
+    let tri: Arc<dyn Shape> = Arc::new(tri);
+    let near = Instance::shared(tri.clone(), Transform::translate(v(2.0, 0.0, 0.0)));
+    let far = Instance::shared(tri.clone(), Transform::translate(v(4.0, 0.0, 0.0)));
+    assert_eq!(Arc::strong_count(&tri), 3);
+    assert!((near.ray_intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap() - 2.0).abs() < 1e-5);
+    assert!((far.ray_intersect(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).unwrap() - 4.0).abs() < 1e-5);
+}
+
+#[test]
+fn scene_tables_take_transforms() {
+    let sphere = "[[sphere]]\ncenter = [0, 0, 0]\nradius = 1\nsurface = \"mirror\"\nspecular = 1\nshininess = 1\n";
+    let file = SceneFile::parse(&format!("{}scale = [1, 2, 1]\ntranslate = [3, 0, 0]\n", sphere)).unwrap();
+    assert_eq!(file.scene.ind_viewing(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), Some(0));
+    assert!(file.scene.ind_viewing(v(0.0, 1.5, 0.0), v(1.0, 0.0, 0.0)).is_some());
+    assert!(file.scene.ind_viewing(v(0.0, 0.0, 1.5), v(1.0, 0.0, 0.0)).is_none());
+
+    let err = SceneFile::parse(&format!("{}scale = [1, 0, 1]\n", sphere)).err().unwrap();
+    assert_eq!(err.to_string(), "line 7: `scale` factors must not be zero");
+    // scaled along x first, then turned onto y
+    let file = SceneFile::parse(&format!("{}scale = [3, 0.5, 0.5]\nrotate = [0, 0, 1, 90]\n", sphere)).unwrap();
+    assert!(file.scene.ind_viewing(v(-5.0, 2.5, 0.0), v(1.0, 0.0, 0.0)).is_some());
+    assert!(file.scene.ind_viewing(v(-5.0, 0.0, 0.0), v(1.0, 0.0, 0.0)).is_some());
+    assert!(file.scene.ind_viewing(v(2.5, -5.0, 0.0), v(0.0, 1.0, 0.0)).is_none());
+
+    let err = SceneFile::parse(&format!("{}rotate = [0, 0, 1]\n", sphere)).err().unwrap();
+    assert!(err.to_string().starts_with("line 7:"));
+    let err = SceneFile::parse(&format!("{}rotate = [0, 0, 0, 30]\n", sphere)).err().unwrap();
+    assert_eq!(err.to_string(), "line 7: `rotate` needs a non-zero axis, then an angle");
+}
@@ -0,0 +1,32 @@
+use raytracer::III_vector::Vec3;
+
+fn v(x: f32, y: f32, z: f32) -> Vec3<f32> {
+    Vec3::new(x, y, z)
+}
+
+#[test]
+fn cross_product_is_right_handed() {
+    let (x, y, z) = (v(1.0, 0.0, 0.0), v(0.0, 1.0, 0.0), v(0.0, 0.0, 1.0));
+    let same = |a: Vec3<f32>, b: Vec3<f32>| (a - b).len() < 1e-6;
+    assert!(same(Vec3::prod(x, y), z));
+    assert!(same(Vec3::prod(y, z), x));
+    assert!(same(Vec3::prod(z, x), y));
+    assert!(same(Vec3::prod(y, x), z.scale(-1.0)));
+
+    // orthogonal to both, every component counts
+    let (a, b) = (v(1.0, 2.0, 3.0), v(-4.0, 0.5, 2.0));
+    let c = Vec3::prod(a, b);
+    assert!(same(c, v(2.5, -14.0, 8.5)));
+    assert!((c * a).abs() < 1e-5 && (c * b).abs() < 1e-5);
+}
+
+#[test]
+fn any_unit_vector_completes_into_a_basis() {
+    for &w in [v(1.0, 0.0, 0.0), v(0.0, 0.0, 1.0), v(0.0, -1.0, 0.0), v(-3.0, 4.0, 12.0).norm()].iter() {
+        let (a, b) = w.orthonormal();
+        assert!((a.len() - 1.0).abs() < 1e-6 && (b.len() - 1.0).abs() < 1e-6, "{} {} {}", w.x, w.y, w.z);
+        assert!((a * b).abs() < 1e-6 && (a * w).abs() < 1e-6 && (b * w).abs() < 1e-6);
+        // right-handed, a x b gives w back
+        assert!((Vec3::prod(a, b) - w).len() < 1e-6);
+    }
+}